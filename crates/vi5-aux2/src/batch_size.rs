@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use aviutl2::log;
+
+const MAX_BATCH_SIZE: usize = 50;
+// vi5-cef-serverのウィンドウ（＝一度に転送できるキャンバス）の大きさ
+const TRANSFER_CANVAS_WIDTH: u32 = 2048;
+const TRANSFER_CANVAS_HEIGHT: u32 = 2040;
+// 新しい計測値の重み
+const SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Default)]
+struct EffectStats {
+    // 1フレームあたりの描画時間（秒）。転送も含む
+    frame_time: Option<f64>,
+    largest_size: (u32, u32),
+}
+
+/// エフェクトごとの計測値からバッチサイズを決める。
+///
+/// プレビューでは1回の描画が`preview_latency_budget_ms`に収まるようにし、
+/// 書き出し中は1回の転送に詰められるだけ詰めてスループットを優先する。
+#[derive(Default)]
+pub struct BatchSizeController {
+    stats: std::sync::Mutex<HashMap<i32, EffectStats>>,
+}
+
+impl BatchSizeController {
+    pub fn record(
+        &self,
+        effect_id: i32,
+        frames: usize,
+        elapsed: std::time::Duration,
+        largest_size: (u32, u32),
+    ) {
+        if frames == 0 {
+            return;
+        }
+        let mut stats = self.stats.lock().unwrap();
+        let stats = stats.entry(effect_id).or_default();
+        let frame_time = elapsed.as_secs_f64() / frames as f64;
+        stats.frame_time = Some(match stats.frame_time {
+            Some(previous) => previous + (frame_time - previous) * SMOOTHING,
+            None => frame_time,
+        });
+        stats.largest_size = largest_size;
+        log::debug!(
+            "Batch stats for effect_id {}: {} frames in {:.1}ms ({:.1}ms/frame, largest {}x{})",
+            effect_id,
+            frames,
+            elapsed.as_secs_f64() * 1000.0,
+            stats.frame_time.unwrap_or_default() * 1000.0,
+            largest_size.0,
+            largest_size.1
+        );
+    }
+
+    pub fn batch_size(&self, effect_id: i32, is_offline: bool) -> usize {
+        let stats = self.stats.lock().unwrap();
+        let Some(stats) = stats.get(&effect_id) else {
+            return 1;
+        };
+        let (width, height) = stats.largest_size;
+        // 1回の転送に収まるフレーム数
+        let per_transfer = ((TRANSFER_CANVAS_WIDTH / width.max(1) / 2)
+            * (TRANSFER_CANVAS_HEIGHT / height.max(1) / 2)) as usize;
+        let Some(frame_time) = stats.frame_time.filter(|&t| t > 0.0) else {
+            return per_transfer.clamp(1, MAX_BATCH_SIZE);
+        };
+        let time_budget = if is_offline {
+            // タイムアウトまでには余裕を持たせる
+            crate::module::RENDER_TIMEOUT.as_secs_f64() / 2.0
+        } else {
+            crate::settings::get().preview_latency_budget_ms as f64 / 1000.0
+        };
+        let per_budget = (time_budget / frame_time) as usize;
+        per_transfer.min(per_budget).clamp(1, MAX_BATCH_SIZE)
+    }
+
+    pub fn clear(&self) {
+        self.stats.lock().unwrap().clear();
+    }
+}
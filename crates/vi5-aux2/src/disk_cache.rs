@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aviutl2::log;
+use image::ImageEncoder;
+
+use crate::render_cache::RenderCacheEntry;
+
+const INDEX_FILE_NAME: &str = "index.json";
+// キーの作り方や、保存する画像の形式を変えたらここも変える
+const KEY_SCHEMA: &[u8] = b"vi5-aux2-disk-cache-v2";
+// インデックスを書き出す間隔。毎フレーム書き出すとエントリ数に比例したI/Oになる
+const INDEX_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+static CURRENT: std::sync::RwLock<Option<Arc<DiskCache>>> = std::sync::RwLock::new(None);
+
+/// プロジェクトフォルダごとに分けた、内容アドレスのディスクキャッシュ。
+pub struct DiskCache {
+    dir: std::path::PathBuf,
+    index: std::sync::Mutex<Index>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+struct Index {
+    budget: u64,
+    total_size: u64,
+    entries: HashMap<String, IndexEntry>,
+    // 最後に書き出してから変更があるか
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    last_saved: Option<std::time::Instant>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+struct IndexEntry {
+    size: u64,
+    width: usize,
+    height: usize,
+    last_access: u64,
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn project_cache_dir(project_dir: &str) -> anyhow::Result<std::path::PathBuf> {
+    let project_dir = std::fs::canonicalize(project_dir)?;
+    let project_hash = blake3::hash(project_dir.to_string_lossy().as_bytes()).to_hex();
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get cache directory"))?
+        .join("vi5_aux2_cache")
+        .join(&project_hash[..16]))
+}
+
+/// プロジェクトフォルダのディスクキャッシュを開き、以降のレンダリングで使うようにする。
+pub fn open_project(project_dir: &str) -> anyhow::Result<()> {
+    let dir = project_cache_dir(project_dir)?;
+    let budget = crate::settings::get().disk_cache_budget();
+    let cache = DiskCache::open(dir, budget)?;
+    *CURRENT.write().unwrap() = Some(Arc::new(cache));
+    Ok(())
+}
+
+pub fn current() -> Option<Arc<DiskCache>> {
+    CURRENT.read().unwrap().clone()
+}
+
+/// 書き出していないインデックスの変更を保存する。
+pub fn flush_current() {
+    if let Some(cache) = current() {
+        cache.flush();
+    }
+}
+
+fn write_str(hasher: &mut blake3::Hasher, value: &str) {
+    hasher.update(&(value.len() as u64).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+/// `RenderRequest`とオブジェクトのソースのバージョンからキャッシュキーを作る。
+///
+/// effect_idはプロジェクトをまたぐと変わるので含めない。
+/// ただしオフラインでは乱数のシードにobject_idが使われるので、その場合だけ含める。
+pub fn cache_key(request: &vi5_cef::RenderRequest, object_version: &str) -> String {
+    let request = crate::module::strip_independent_inputs(request);
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(KEY_SCHEMA);
+    write_str(&mut hasher, &request.object);
+    write_str(&mut hasher, object_version);
+    hasher.update(&[request.is_offline as u8]);
+    if request.is_offline {
+        hasher.update(&request.object_id.to_le_bytes());
+        hasher.update(&request.seed.to_le_bytes());
+    }
+
+    let frame_info = &request.frame_info;
+    for value in [
+        frame_info.x,
+        frame_info.y,
+        frame_info.z,
+        frame_info.current_time,
+        frame_info.total_time,
+        frame_info.framerate,
+        frame_info.global_time,
+    ] {
+        hasher.update(&value.to_bits().to_le_bytes());
+    }
+    for value in [
+        frame_info.screen_width,
+        frame_info.screen_height,
+        frame_info.current_frame,
+        frame_info.total_frames,
+        frame_info.global_frame,
+    ] {
+        hasher.update(&(value as u64).to_le_bytes());
+    }
+
+    let mut parameters = request.parameters.iter().collect::<Vec<_>>();
+    parameters.sort_by(|a, b| a.key.cmp(&b.key));
+    hasher.update(&(parameters.len() as u64).to_le_bytes());
+    for parameter in parameters {
+        write_str(&mut hasher, &parameter.key);
+        match &parameter.value {
+            vi5_cef::ParameterValue::Str(value) => {
+                hasher.update(&[0]);
+                write_str(&mut hasher, value);
+            }
+            vi5_cef::ParameterValue::Text(value) => {
+                hasher.update(&[1]);
+                write_str(&mut hasher, value);
+            }
+            vi5_cef::ParameterValue::Number(value) => {
+                hasher.update(&[2]);
+                hasher.update(&value.to_bits().to_le_bytes());
+            }
+            vi5_cef::ParameterValue::Bool(value) => {
+                hasher.update(&[3, *value as u8]);
+            }
+            vi5_cef::ParameterValue::Color(value) => {
+                hasher.update(&[4, value.r, value.g, value.b, value.a]);
+            }
+            vi5_cef::ParameterValue::Select(value) => {
+                hasher.update(&[5]);
+                write_str(&mut hasher, value);
+            }
+            vi5_cef::ParameterValue::File(value) => {
+                hasher.update(&[6]);
+                write_str(&mut hasher, &value.path);
+                hasher.update(&value.modified_ms.to_le_bytes());
+            }
+            vi5_cef::ParameterValue::Vector2 { x, y } => {
+                hasher.update(&[7]);
+                hasher.update(&x.to_bits().to_le_bytes());
+                hasher.update(&y.to_bits().to_le_bytes());
+            }
+            vi5_cef::ParameterValue::Vector3 { x, y, z } => {
+                hasher.update(&[8]);
+                hasher.update(&x.to_bits().to_le_bytes());
+                hasher.update(&y.to_bits().to_le_bytes());
+                hasher.update(&z.to_bits().to_le_bytes());
+            }
+            vi5_cef::ParameterValue::Range { start, end } => {
+                hasher.update(&[9]);
+                hasher.update(&start.to_bits().to_le_bytes());
+                hasher.update(&end.to_bits().to_le_bytes());
+            }
+        }
+    }
+    if let Some(input_image) = &request.input_image {
+        hasher.update(&(input_image.width as u64).to_le_bytes());
+        hasher.update(&(input_image.height as u64).to_le_bytes());
+        hasher.update(&input_image.image_data);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+impl DiskCache {
+    fn open(dir: std::path::PathBuf, budget: u64) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let index_path = dir.join(INDEX_FILE_NAME);
+        let mut index = match std::fs::read_to_string(&index_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Disk cache index is corrupted, starting over: {}", e);
+                Index::default()
+            }),
+            Err(_) => Index::default(),
+        };
+        // インデックスにないファイルや、書き込み途中で落ちた一時ファイルは誰も参照できないので消す
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                let _ = std::fs::remove_file(&path);
+            } else if path.extension().is_some_and(|ext| ext == "png")
+                && !path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| index.entries.contains_key(stem))
+            {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        index.budget = budget;
+        log::info!(
+            "Opened disk cache at {:?} ({} entries, {:.1} MiB)",
+            dir,
+            index.entries.len(),
+            index.total_size as f64 / (1024.0 * 1024.0)
+        );
+        let cache = Self {
+            dir,
+            index: std::sync::Mutex::new(index),
+        };
+        {
+            let mut index = cache.index.lock().unwrap();
+            cache.evict(&mut index);
+            cache.save_if_dirty(&mut index);
+        }
+        Ok(cache)
+    }
+
+    fn entry_path(&self, key: &str) -> std::path::PathBuf {
+        self.dir.join(format!("{}.png", key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<RenderCacheEntry> {
+        {
+            let mut index = self.index.lock().unwrap();
+            let entry = index.entries.get_mut(key)?;
+            entry.last_access = unix_time();
+            index.dirty = true;
+            self.save_if_due(&mut index);
+        }
+        // デコードは重いので、ロックを持たずに行う
+        match image::open(self.entry_path(key)) {
+            Ok(image) => {
+                let image = image.into_rgba8();
+                Some(RenderCacheEntry {
+                    width: image.width() as usize,
+                    height: image.height() as usize,
+                    image_data: image.into_raw(),
+                })
+            }
+            Err(e) => {
+                log::warn!("Failed to read disk cache entry {}: {}", key, e);
+                let mut index = self.index.lock().unwrap();
+                if let Some(entry) = index.entries.remove(key) {
+                    index.total_size -= entry.size;
+                    index.dirty = true;
+                }
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: &str, entry: &RenderCacheEntry) -> anyhow::Result<()> {
+        // 途中で落ちても壊れたファイルが残らないよう、一時ファイルに書いてから置き換える
+        let path = self.entry_path(key);
+        let temp_path = path.with_extension("png.tmp");
+        {
+            let file = std::io::BufWriter::new(std::fs::File::create(&temp_path)?);
+            image::codecs::png::PngEncoder::new_with_quality(
+                file,
+                image::codecs::png::CompressionType::Fast,
+                image::codecs::png::FilterType::Adaptive,
+            )
+            .write_image(
+                &entry.image_data,
+                entry.width as u32,
+                entry.height as u32,
+                image::ExtendedColorType::Rgba8,
+            )?;
+        }
+        std::fs::rename(&temp_path, &path)?;
+        let size = std::fs::metadata(&path)?.len();
+
+        let mut index = self.index.lock().unwrap();
+        if let Some(previous) = index.entries.insert(
+            key.to_string(),
+            IndexEntry {
+                size,
+                width: entry.width,
+                height: entry.height,
+                last_access: unix_time(),
+            },
+        ) {
+            index.total_size -= previous.size;
+        }
+        index.total_size += size;
+        index.dirty = true;
+        self.evict(&mut index);
+        self.save_if_due(&mut index);
+        Ok(())
+    }
+
+    pub fn set_budget(&self, budget: u64) {
+        let mut index = self.index.lock().unwrap();
+        index.budget = budget;
+        self.evict(&mut index);
+        self.save_if_dirty(&mut index);
+    }
+
+    pub fn flush(&self) {
+        self.save_if_dirty(&mut self.index.lock().unwrap());
+    }
+
+    pub fn clear(&self) -> anyhow::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        for key in index.entries.keys() {
+            let _ = std::fs::remove_file(self.entry_path(key));
+        }
+        index.entries.clear();
+        index.total_size = 0;
+        self.save_index(&mut index)?;
+        log::info!("Cleared disk cache at {:?}", self.dir);
+        Ok(())
+    }
+
+    fn evict(&self, index: &mut Index) {
+        if index.total_size > index.budget {
+            let mut entries = index
+                .entries
+                .iter()
+                .map(|(key, entry)| (entry.last_access, key.clone()))
+                .collect::<Vec<_>>();
+            entries.sort();
+            for (_, key) in entries {
+                if index.total_size <= index.budget {
+                    break;
+                }
+                let entry = index.entries.remove(&key).unwrap();
+                index.total_size -= entry.size;
+                let _ = std::fs::remove_file(self.entry_path(&key));
+                index.dirty = true;
+            }
+        }
+    }
+
+    fn save_if_due(&self, index: &mut Index) {
+        if index
+            .last_saved
+            .is_none_or(|last_saved| last_saved.elapsed() >= INDEX_SAVE_INTERVAL)
+        {
+            self.save_if_dirty(index);
+        }
+    }
+
+    fn save_if_dirty(&self, index: &mut Index) {
+        if !index.dirty {
+            return;
+        }
+        if let Err(e) = self.save_index(index) {
+            log::error!("Failed to save disk cache index: {}", e);
+        }
+    }
+
+    fn save_index(&self, index: &mut Index) -> anyhow::Result<()> {
+        let path = self.dir.join(INDEX_FILE_NAME);
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, serde_json::to_vec(index)?)?;
+        std::fs::rename(&temp_path, &path)?;
+        index.dirty = false;
+        index.last_saved = Some(std::time::Instant::now());
+        Ok(())
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
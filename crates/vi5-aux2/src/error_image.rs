@@ -0,0 +1,214 @@
+use ab_glyph::{Font, ScaleFont};
+use aviutl2::log;
+
+use crate::render_cache::RenderCacheEntry;
+
+const MAX_MESSAGE_CHARS: usize = 300;
+const BACKGROUND: [u8; 4] = [255, 193, 7, 230];
+const STRIPE: [u8; 4] = [33, 33, 33, 230];
+const TEXT: [u8; 3] = [33, 33, 33];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // JS側のオブジェクトがエラーを返した
+    Script,
+    Timeout,
+    ServerUnavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Script => "E_SCRIPT",
+            ErrorCode::Timeout => "E_TIMEOUT",
+            ErrorCode::ServerUnavailable => "E_SERVER",
+            ErrorCode::Internal => "E_INTERNAL",
+        }
+    }
+}
+
+/// エラーコードを付けたエラー。`anyhow::Error`から取り出して画像に描く。
+#[derive(Debug)]
+pub struct RenderFailure {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl RenderFailure {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for RenderFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.code.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for RenderFailure {}
+
+static FONT: std::sync::LazyLock<Option<ab_glyph::FontVec>> = std::sync::LazyLock::new(|| {
+    let fonts_dir = std::path::PathBuf::from(
+        std::env::var_os("WINDIR").unwrap_or_else(|| r"C:\Windows".into()),
+    )
+    .join("Fonts");
+    // 日本語のエラーメッセージも描けるよう、日本語フォントを優先する
+    for name in ["meiryo.ttc", "YuGothM.ttc", "msgothic.ttc", "segoeui.ttf"] {
+        let Ok(data) = std::fs::read(fonts_dir.join(name)) else {
+            continue;
+        };
+        match ab_glyph::FontVec::try_from_vec_and_index(data, 0) {
+            Ok(font) => return Some(font),
+            Err(e) => log::warn!("Failed to load font {}: {}", name, e),
+        }
+    }
+    log::warn!("No font found for error images, text will not be drawn");
+    None
+});
+
+fn blend(image: &mut [u8], width: usize, x: i32, y: i32, coverage: f32) {
+    let Ok(x) = usize::try_from(x) else { return };
+    let Ok(y) = usize::try_from(y) else { return };
+    if x >= width || y * width + x >= image.len() / 4 {
+        return;
+    }
+    let pixel = &mut image[(y * width + x) * 4..][..4];
+    let coverage = coverage.clamp(0.0, 1.0);
+    for (channel, text) in pixel.iter_mut().zip(TEXT) {
+        *channel = (*channel as f32 * (1.0 - coverage) + text as f32 * coverage) as u8;
+    }
+    pixel[3] = pixel[3].max((coverage * 255.0) as u8);
+}
+
+/// `max_width`に収まるように折り返す。
+fn wrap(
+    font: &ab_glyph::PxScaleFont<&ab_glyph::FontVec>,
+    text: &str,
+    max_width: f32,
+) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0.0;
+        for c in paragraph.chars() {
+            let advance = font.h_advance(font.glyph_id(c));
+            if line_width + advance > max_width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0.0;
+            }
+            line.push(c);
+            line_width += advance;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn draw_line(
+    image: &mut [u8],
+    width: usize,
+    font: &ab_glyph::PxScaleFont<&ab_glyph::FontVec>,
+    text: &str,
+    x: f32,
+    baseline: f32,
+) {
+    let mut caret = x;
+    for c in text.chars() {
+        let glyph_id = font.glyph_id(c);
+        let glyph =
+            glyph_id.with_scale_and_position(font.scale(), ab_glyph::point(caret, baseline));
+        caret += font.h_advance(glyph_id);
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                blend(
+                    image,
+                    width,
+                    bounds.min.x as i32 + gx as i32,
+                    bounds.min.y as i32 + gy as i32,
+                    coverage,
+                );
+            });
+        }
+    }
+}
+
+/// オブジェクト名・エラーコード・メッセージを描いた警告画像を作る。
+pub fn render(
+    object_name: &str,
+    error: &anyhow::Error,
+    width: usize,
+    height: usize,
+) -> RenderCacheEntry {
+    let (code, message) = match error.downcast_ref::<RenderFailure>() {
+        Some(failure) => (failure.code, failure.message.clone()),
+        None => (ErrorCode::Internal, format!("{:#}", error)),
+    };
+    let message = if message.chars().count() > MAX_MESSAGE_CHARS {
+        format!(
+            "{}…",
+            message.chars().take(MAX_MESSAGE_CHARS).collect::<String>()
+        )
+    } else {
+        message
+    };
+
+    let width = width.max(1);
+    let height = height.max(1);
+    let mut image_data = Vec::with_capacity(width * height * 4);
+    // 上下に警告の縞模様を入れる
+    let stripe_height = (height / 16).clamp(2, 16);
+    for y in 0..height {
+        for x in 0..width {
+            let is_stripe = (y < stripe_height || y >= height - stripe_height)
+                && (x + y) / stripe_height % 2 == 0;
+            image_data.extend_from_slice(if is_stripe { &STRIPE } else { &BACKGROUND });
+        }
+    }
+
+    if let Some(font) = FONT.as_ref() {
+        let scale = (height as f32 / 12.0).clamp(10.0, 32.0);
+        let margin = stripe_height as f32 + scale / 2.0;
+        let title_font = font.as_scaled(scale * 1.25);
+        let body_font = font.as_scaled(scale);
+        let max_width = width as f32 - margin * 2.0;
+        let max_y = height as f32 - margin;
+
+        let mut baseline = margin + title_font.ascent();
+        draw_line(
+            &mut image_data,
+            width,
+            &title_font,
+            object_name,
+            margin,
+            baseline,
+        );
+        baseline += title_font.height() + title_font.line_gap();
+        draw_line(
+            &mut image_data,
+            width,
+            &body_font,
+            code.as_str(),
+            margin,
+            baseline,
+        );
+        for line in wrap(&body_font, &message, max_width) {
+            baseline += body_font.height() + body_font.line_gap();
+            if baseline - body_font.descent() > max_y {
+                break;
+            }
+            draw_line(&mut image_data, width, &body_font, &line, margin, baseline);
+        }
+    }
+
+    RenderCacheEntry {
+        image_data,
+        width,
+        height,
+    }
+}
@@ -1,4 +1,15 @@
+mod batch_size;
+mod disk_cache;
+mod error_image;
+mod lua_call;
 mod module;
+mod prefetch;
+mod render_cache;
+mod script_generator;
+mod script_manifest;
+#[cfg(test)]
+mod script_tests;
+mod settings;
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
@@ -13,6 +24,7 @@ type Vi5Server = Arc<
         Option<(
             Arc<tokio::sync::Mutex<tokio::process::Child>>,
             vi5_cef::Client,
+            vi5_cef::HeartbeatHandle,
         )>,
     >,
 >;
@@ -29,13 +41,41 @@ struct Vi5Aux2 {
 
 static CURRENT_PROJECT_FILE: std::sync::Mutex<Option<std::path::PathBuf>> =
     std::sync::Mutex::new(None);
-static VAR_PREFIX: &str = "VI5_AUX2_";
-const VI5_CEF_SERVER_PORT: u16 = 50051;
+// 他のユーザーやプロセスから接続されないよう、TCPではなくプロセスごとの名前付きパイプとトークンを使う
+static VI5_CEF_SERVER_PIPE_NAME: std::sync::LazyLock<String> =
+    std::sync::LazyLock::new(|| format!(r"\\.\pipe\vi5-cef-server-{}", std::process::id()));
+static VI5_CEF_SERVER_TOKEN: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+});
+const VI5_CEF_SERVER_LEASE_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(30);
+const VI5_CEF_SERVER_SHUTDOWN_DEADLINE: std::time::Duration = std::time::Duration::from_secs(5);
+
+// 再描画の要求中。裏で描画が続けて終わっても、編集処理は1回だけ呼ぶ
+static REDRAW_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// AviUtl2に画面の再描画を要求する。
+fn request_redraw() {
+    if REDRAW_REQUESTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    // SDK（plugin2.h）のEDIT_HANDLE::call_edit_sectionは、編集情報を排他制御してからコールバックを呼び、
+    // 抜けたときに編集内容を反映する。出力中など編集できないときは失敗する
+    if let Err(e) = EDIT_HANDLE.call_edit_section(|_edit_section| {}) {
+        log::warn!("Failed to request redraw: {}", e);
+    }
+    REDRAW_REQUESTED.store(false, Ordering::Release);
+}
 
 fn get_script_dir(project_name: &str) -> std::path::PathBuf {
     aviutl2::config::app_data_path()
         .join("Script")
-        .join(format!("vi5.aux2_{}", project_name))
+        .join(format!(
+            "vi5.aux2_{}",
+            script_generator::sanitize_file_name(project_name)
+        ))
 }
 
 static EDIT_HANDLE: aviutl2::generic::GlobalEditHandle = aviutl2::generic::GlobalEditHandle::new();
@@ -66,6 +106,49 @@ impl Vi5Aux2 {
         self.set_project_dir(dir_str)?;
         Ok(())
     }
+
+    #[config(name = "[vi5.aux2] 設定ファイルを開く")]
+    fn open_settings(&mut self, _hwnd: aviutl2::Win32WindowHandle) -> anyhow::Result<()> {
+        use std::os::windows::process::CommandExt;
+
+        let path = crate::settings::ensure_file()?;
+        std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("start")
+            .arg("")
+            .arg(&path)
+            .creation_flags(0x08000000) // CREATE_NO_WINDOW
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("設定ファイルを開けませんでした: {}", e))?;
+        Ok(())
+    }
+
+    #[config(name = "[vi5.aux2] 設定を再読み込み")]
+    fn reload_settings(&mut self, _hwnd: aviutl2::Win32WindowHandle) -> anyhow::Result<()> {
+        let settings = crate::settings::reload();
+        crate::module::set_render_cache_budget(settings.render_cache_budget());
+        if let Some(disk_cache) = crate::disk_cache::current() {
+            disk_cache.set_budget(settings.disk_cache_budget());
+        }
+        Ok(())
+    }
+
+    #[config(name = "[vi5.aux2] このプロジェクトのディスクキャッシュを削除")]
+    fn clear_disk_cache(&mut self, _hwnd: aviutl2::Win32WindowHandle) -> anyhow::Result<()> {
+        let disk_cache = crate::disk_cache::current()
+            .ok_or_else(|| anyhow::anyhow!("プロジェクトフォルダが設定されていません"))?;
+        disk_cache
+            .clear()
+            .map_err(|e| anyhow::anyhow!("ディスクキャッシュを削除できませんでした: {}", e))?;
+        crate::module::clear_render_cache();
+        let _ = native_dialog::DialogBuilder::message()
+            .set_title("vi5.aux2")
+            .set_text("ディスクキャッシュを削除しました。")
+            .set_level(native_dialog::MessageLevel::Info)
+            .alert()
+            .show();
+        Ok(())
+    }
 }
 impl Vi5Aux2 {
     fn set_project_dir(&mut self, dir: String) -> anyhow::Result<()> {
@@ -115,25 +198,29 @@ impl Vi5Aux2 {
 
         let mut server_guard = server.lock().await;
         if server_guard.is_none() {
-            let (child, client) = Self::start_vi5_cef_server().await.inspect_err(|e| {
-                let _ = native_dialog::DialogBuilder::message()
-                    .set_title("vi5.aux2")
-                    .set_text(format!("vi5-cef サーバーの起動に失敗しました:\n{}", e))
-                    .set_level(native_dialog::MessageLevel::Error)
-                    .alert()
-                    .show();
-            })?;
+            let (child, client, heartbeat) =
+                Self::start_vi5_cef_server().await.inspect_err(|e| {
+                    let _ = native_dialog::DialogBuilder::message()
+                        .set_title("vi5.aux2")
+                        .set_text(format!("vi5-cef サーバーの起動に失敗しました:\n{}", e))
+                        .set_level(native_dialog::MessageLevel::Error)
+                        .alert()
+                        .show();
+                })?;
             let child = Arc::new(tokio::sync::Mutex::new(child));
             Self::spawn_vi5_cef_exit_logger(child.clone());
-            *server_guard = Some((child, client));
+            *server_guard = Some((child, client, heartbeat));
         }
-        let client = server_guard.as_mut().map(|(_, client)| client).unwrap();
+        let client = server_guard.as_mut().map(|(_, client, _)| client).unwrap();
 
         let info = client
             .initialize(&dir, Some(std::time::Duration::from_secs(60)))
             .await
             .map_err(|e| anyhow::anyhow!("vi5-cef クライアントの初期化に失敗しました: {}", e))?;
         log::info!("vi5-cef initialized successfully.");
+        if let Err(e) = crate::disk_cache::open_project(&dir) {
+            log::error!("Failed to open disk cache: {}", e);
+        }
         if !notifications_started.swap(true, Ordering::SeqCst) {
             tokio::spawn(Self::notification_listener_task(
                 info.project_name.clone(),
@@ -163,136 +250,42 @@ impl Vi5Aux2 {
             requires_restart = true;
         }
 
+        let mut used_file_names = std::collections::BTreeSet::new();
+        let mut generated_files = std::collections::BTreeMap::new();
         for object in object_infos {
-            let base_script = include_str!("./script.lua").to_string();
-            let param_defs = object
-                .parameter_definitions
-                .iter()
-                .map(|param| {
-                    let key = &param.key;
-                    let label = &param.label;
-                    match param.parameter_type {
-                        vi5_cef::ParameterType::String => {
-                            let default_value = match &param.default_value {
-                                Some(vi5_cef::Parameter {
-                                    value: vi5_cef::ParameterValue::Str(value),
-                                    ..
-                                }) => value.clone(),
-                                _ => "".to_string(),
-                            };
-                            let default_value = serde_json::to_string(&default_value).unwrap();
-                            format!(r#"--value@{VAR_PREFIX}{key}:{label},{default_value}"#)
-                        }
-                        vi5_cef::ParameterType::Text => {
-                            let default_value = match &param.default_value {
-                                Some(vi5_cef::Parameter {
-                                    value: vi5_cef::ParameterValue::Text(value),
-                                    ..
-                                }) => value.clone(),
-                                _ => "".to_string(),
-                            };
-                            let default_value = serde_json::to_string(&default_value).unwrap();
-                            format!(r#"--text@{VAR_PREFIX}{key}:{label},{default_value}"#)
-                        }
-                        vi5_cef::ParameterType::Boolean => {
-                            let default_value = match &param.default_value {
-                                Some(vi5_cef::Parameter {
-                                    value: vi5_cef::ParameterValue::Bool(value),
-                                    ..
-                                }) => *value,
-                                _ => false,
-                            };
-                            let default_value = if default_value { "true" } else { "false" };
-                            format!(r#"--check@{VAR_PREFIX}{key}:{label},{default_value}"#)
-                        }
-                        vi5_cef::ParameterType::Number { step, min, max } => {
-                            let min_str = min.to_string();
-                            let max_str = max.to_string();
-                            let step = step.as_str();
-                            let default_value = match &param.default_value {
-                                Some(vi5_cef::Parameter {
-                                    value: vi5_cef::ParameterValue::Number(value),
-                                    ..
-                                }) => *value,
-                                _ => min,
-                            };
-                            format!(
-                                r#"--track@{VAR_PREFIX}{key}:{label},{min_str},{max_str},{default_value},{step}"#
-                            )
-                        }
-                        vi5_cef::ParameterType::Color => {
-                            let default_value = match &param.default_value {
-                                Some(vi5_cef::Parameter {
-                                    value: vi5_cef::ParameterValue::Color(value),
-                                    ..
-                                }) => {
-                                    if value.a == 0 {
-                                        "nil".to_string()
-                                    } else {
-                                        let r = value.r as u32;
-                                        let g = value.g as u32;
-                                        let b = value.b as u32;
-                                        format!("0x{:02X}{:02X}{:02X}", r, g, b)
-                                    }
-                                }
-                                _ => "nil".to_string(),
-                            };
-                            format!(r#"--color@{VAR_PREFIX}{key}:{label},{default_value}"#)
-                        }
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-            let keys = object
-                .parameter_definitions
-                .iter()
-                .map(|param| serde_json::to_string(&param.key).unwrap())
-                .collect::<Vec<_>>()
-                .join(",");
-            let values = object
-                .parameter_definitions
-                .iter()
-                .map(|param| {
-                    let key = &param.key;
-                    match param.parameter_type {
-                        vi5_cef::ParameterType::Number { .. } => {
-                            format!(r#"{VAR_PREFIX}{key}"#)
-                        }
-                        _ => {
-                            format!(r#"{VAR_PREFIX}{key}"#)
-                        }
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join(",");
-            let types = object
-                .parameter_definitions
-                .iter()
-                .map(|param| match param.parameter_type {
-                    vi5_cef::ParameterType::String => r#""Str""#.to_string(),
-                    vi5_cef::ParameterType::Text => r#""Text""#.to_string(),
-                    vi5_cef::ParameterType::Boolean => r#""Bool""#.to_string(),
-                    vi5_cef::ParameterType::Number { .. } => r#""Number""#.to_string(),
-                    vi5_cef::ParameterType::Color => r#""Color""#.to_string(),
-                })
-                .collect::<Vec<_>>()
-                .join(",");
-
-            let script_content = base_script
-                .replace("--PARAMETER_DEFINITIONS--", param_defs.as_str())
-                .replace(
-                    "--LABEL--",
-                    format!("--label:vi5.aux2\\{}", project_name).as_str(),
-                )
-                .replace("--MODULE_NAME--", module_name.as_str())
-                .replace("--PARAMETER_KEYS--", keys.as_str())
-                .replace("--PARAMETER_VALUES--", values.as_str())
-                .replace(r#"--PARAMETER_TYPES--"#, types.as_str())
-                .replace(
-                    r#""--OBJECT_ID--""#,
-                    serde_json::to_string(&object.id).unwrap().as_str(),
-                );
-            let script_path = script_dir.join(format!("{}.obj2", object.label));
+            let script = match script_generator::generate(project_name, &module_name, object) {
+                Ok(script) => script,
+                Err(e) => {
+                    log::error!(
+                        "Failed to generate script for object '{}': {}",
+                        object.id,
+                        e
+                    );
+                    // 前回のスクリプトは古いものとして消さずに残す
+                    used_file_names.insert(script_generator::file_name(object).to_lowercase());
+                    continue;
+                }
+            };
+            let script_content = script.content;
+            // Windowsのファイル名は大文字小文字を区別しないので、ラベルが違っても同じファイルになりうる
+            let mut file_name = script.file_name.clone();
+            let mut suffix = 2;
+            while !used_file_names.insert(file_name.to_lowercase()) {
+                let (stem, extension) = script
+                    .file_name
+                    .rsplit_once('.')
+                    .unwrap_or((script.file_name.as_str(), ""));
+                file_name = format!("{} ({}).{}", stem, suffix, extension);
+                suffix += 1;
+            }
+            let script_path = script_dir.join(&file_name);
+            generated_files.insert(
+                file_name,
+                script_manifest::ManifestEntry {
+                    object_id: object.id.clone(),
+                    hash: script_manifest::hash_content(&script_content),
+                },
+            );
             log::info!(
                 "Loaded script for object '{}': {:?}",
                 object.id,
@@ -347,6 +340,13 @@ impl Vi5Aux2 {
             }
         }
 
+        // 古いスクリプトを片付けられなくても、新しいスクリプトの反映は案内する
+        match Self::remove_stale_scripts(&script_dir, &used_file_names, generated_files).await {
+            Ok(true) => requires_restart = true,
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to remove stale scripts: {:#}", e),
+        }
+
         if requires_restart {
             log::info!("Script directory updated requiring restart.");
             let will_restart = native_dialog::DialogBuilder::message()
@@ -376,10 +376,78 @@ impl Vi5Aux2 {
         Ok(())
     }
 
-    async fn start_vi5_cef_server() -> anyhow::Result<(tokio::process::Child, vi5_cef::Client)> {
+    /// オブジェクトの一覧から消えたスクリプトを削除・アーカイブし、マニフェストを更新する。
+    /// スクリプトフォルダが変わった場合は`true`を返す。
+    async fn remove_stale_scripts(
+        script_dir: &std::path::Path,
+        current_file_names: &std::collections::BTreeSet<String>,
+        generated_files: std::collections::BTreeMap<String, script_manifest::ManifestEntry>,
+    ) -> anyhow::Result<bool> {
+        let previous_manifest = script_manifest::ScriptManifest::load(script_dir);
+        let stale = previous_manifest.find_stale(script_dir, current_file_names)?;
+        let mut changed = false;
+
+        for file_name in &stale.unmodified {
+            tokio::fs::remove_file(script_dir.join(file_name)).await?;
+            log::info!("Removed stale script {:?}", file_name);
+            changed = true;
+        }
+        if !stale.modified.is_empty() {
+            let archive_dir = script_manifest::archive(script_dir, &stale.modified)?;
+            log::warn!(
+                "Archived {} edited stale scripts to {:?}",
+                stale.modified.len(),
+                archive_dir
+            );
+            changed = true;
+        }
+
+        // 生成に失敗して残したスクリプトは、次回も生成したものとして扱う
+        let files = previous_manifest.with_kept_entries(generated_files, current_file_names);
+        let mut kept = previous_manifest
+            .kept
+            .into_iter()
+            .filter(|file_name| script_dir.join(file_name).exists())
+            .collect::<std::collections::BTreeSet<_>>();
+        if !stale.foreign.is_empty() {
+            let will_remove = native_dialog::DialogBuilder::message()
+                .set_title("vi5.aux2")
+                .set_text(format!(
+                    "以下のスクリプトはvi5.aux2が生成したものではなく、現在のオブジェクトにも対応していません。\n\n{}\n\n削除しますか？（ファイルはアーカイブフォルダに移動されます）",
+                    stale.foreign.join("\n")
+                ))
+                .confirm()
+                .spawn()
+                .await?;
+            if will_remove {
+                let archive_dir = script_manifest::archive(script_dir, &stale.foreign)?;
+                log::info!(
+                    "Archived {} foreign scripts to {:?}",
+                    stale.foreign.len(),
+                    archive_dir
+                );
+                changed = true;
+            } else {
+                log::info!("Keeping foreign scripts: {:?}", stale.foreign);
+                kept.extend(stale.foreign);
+            }
+        }
+
+        script_manifest::ScriptManifest { files, kept }.save(script_dir)?;
+        Ok(changed)
+    }
+
+    async fn start_vi5_cef_server() -> anyhow::Result<(
+        tokio::process::Child,
+        vi5_cef::Client,
+        vi5_cef::HeartbeatHandle,
+    )> {
         let mut path = std::env::var("PATH").unwrap_or_default();
         path.push_str(";C:\\Users\\seven\\.local\\share\\cef");
-        log::info!("Starting vi5-cef server on port {}", VI5_CEF_SERVER_PORT);
+        log::info!(
+            "Starting vi5-cef server on {}",
+            VI5_CEF_SERVER_PIPE_NAME.as_str()
+        );
         // TODO: 実行ファイルのパスを適切に設定する
         let cef_server_path = std::path::PathBuf::from(format!(
             "e:/aviutl2/vi5.aux2/target/{}/vi5-cef-server.exe",
@@ -390,14 +458,17 @@ impl Vi5Aux2 {
             }
         ));
         let mut child = tokio::process::Command::new(&cef_server_path)
-            .arg("--port")
-            .arg(VI5_CEF_SERVER_PORT.to_string())
+            .arg("--local-socket")
+            .arg(VI5_CEF_SERVER_PIPE_NAME.as_str())
             .arg("--hardware-acceleration")
             .arg("--parent-process")
             .arg(std::process::id().to_string())
+            .arg("--lease-grace-period")
+            .arg(VI5_CEF_SERVER_LEASE_GRACE_PERIOD.as_secs().to_string())
             .env("PATH", path)
             .env("NO_COLOR", "1")
             .env("RUST_LOG", "info,vi5_cef=trace")
+            .env("VI5_CEF_SERVER_TOKEN", VI5_CEF_SERVER_TOKEN.as_str())
             // NOTE: C:\Windows\System32 で起動するとなぜかlibcef.dllを見つけられなくて落ちるので、カレントディレクトリを実行ファイルのディレクトリにする
             .current_dir(cef_server_path.parent().unwrap())
             .stdout(std::process::Stdio::piped())
@@ -426,18 +497,33 @@ impl Vi5Aux2 {
                 }
             }
         });
-        let client = tokio::select! {
+        let mut client = tokio::select! {
             code = child.wait() => {
                 anyhow::bail!("初期化に失敗しました (exit code: {:?})", code);
             }
 
-            res = vi5_cef::Client::connect(
-                format!("http://localhost:{}", VI5_CEF_SERVER_PORT)
-            ) => {
-                res.map_err(anyhow::Error::from)
-            }
+            res = Self::connect_vi5_cef_server() => res,
         }?;
-        Ok((child, client))
+        let lease = client.heartbeat().await.map_err(|e| {
+            anyhow::anyhow!("vi5-cef サーバーへのハートビートに失敗しました: {}", e)
+        })?;
+        let heartbeat_interval = lease
+            .lease_grace_period
+            .unwrap_or(VI5_CEF_SERVER_LEASE_GRACE_PERIOD)
+            / 3;
+        log::info!(
+            "Sending heartbeats to vi5-cef server every {:?}",
+            heartbeat_interval
+        );
+        let heartbeat = client.spawn_heartbeat(heartbeat_interval);
+        Ok((child, client, heartbeat))
+    }
+
+    async fn connect_vi5_cef_server() -> anyhow::Result<vi5_cef::Client> {
+        let client = vi5_cef::Client::connect_local(VI5_CEF_SERVER_PIPE_NAME.as_str())
+            .await?
+            .with_token(&VI5_CEF_SERVER_TOKEN)?;
+        Ok(client)
     }
 
     async fn notification_listener_task(
@@ -457,16 +543,13 @@ impl Vi5Aux2 {
         let _guard = NotificationGuard {
             started: notifications_started.clone(),
         };
-        let mut client =
-            match vi5_cef::Client::connect(format!("http://localhost:{}", VI5_CEF_SERVER_PORT))
-                .await
-            {
-                Ok(client) => client,
-                Err(e) => {
-                    log::error!("Failed to connect for notifications: {}", e);
-                    return;
-                }
-            };
+        let mut client = match Self::connect_vi5_cef_server().await {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Failed to connect for notifications: {}", e);
+                return;
+            }
+        };
 
         let mut stream = match client.subscribe_notifications().await {
             Ok(stream) => stream,
@@ -496,6 +579,7 @@ impl Vi5Aux2 {
                             "Received object infos notification with {} objects",
                             object_infos.object_infos.len()
                         );
+                        crate::module::set_object_infos(&object_infos.object_infos);
                         if let Err(e) =
                             Self::update_script_dir(&project_name, &object_infos.object_infos).await
                         {
@@ -553,17 +637,6 @@ impl Vi5Aux2 {
             runtime: self.runtime.clone(),
         }
     }
-
-    pub async fn with_client<F, R>(&self, f: F) -> anyhow::Result<R>
-    where
-        F: AsyncFnOnce(&mut vi5_cef::Client) -> anyhow::Result<R>,
-    {
-        let mut server_guard = self.server.lock().await;
-        let Some((_, client)) = server_guard.as_mut() else {
-            anyhow::bail!("vi5-cef server is not running")
-        };
-        f(client).await
-    }
 }
 
 struct RuntimeHandle {
@@ -634,7 +707,7 @@ impl aviutl2::generic::GenericPlugin for Vi5Aux2 {
             let server = Arc::clone(&self.server);
             async move {
                 let mut server = server.lock().await;
-                let Some((_, client)) = server.as_mut() else {
+                let Some((_, client, _)) = server.as_mut() else {
                     log::warn!("vi5-cef server is not running, cannot purge cache");
                     return;
                 };
@@ -659,13 +732,39 @@ impl aviutl2::generic::GenericPlugin for Vi5Aux2 {
 
 impl Drop for Vi5Aux2 {
     fn drop(&mut self) {
-        if let Some((child, mut client)) = self.server.blocking_lock().take() {
+        crate::disk_cache::flush_current();
+        if let Some((child, mut client, heartbeat)) = self.server.blocking_lock().take() {
             log::info!("Shutting down vi5-cef server...");
-            futures::executor::block_on(async {
-                let _ = client.shutdown().await;
+            drop(heartbeat);
+            let shutdown = async {
+                match client
+                    .shutdown(Some(VI5_CEF_SERVER_SHUTDOWN_DEADLINE))
+                    .await
+                {
+                    Ok(report) => {
+                        log::info!(
+                            "vi5-cef server shut down (drained: {}, cancelled: {}, timed out: {})",
+                            report.drained_renders,
+                            report.cancelled_renders,
+                            report.timed_out
+                        );
+                    }
+                    Err(e) => {
+                        log::error!("Failed to shut down vi5-cef server gracefully: {}", e);
+                    }
+                }
                 let mut child = child.lock().await;
-                let _ = child.kill().await;
-            });
+                if tokio::time::timeout(std::time::Duration::from_secs(5), child.wait())
+                    .await
+                    .is_err()
+                {
+                    log::warn!("vi5-cef server did not exit in time, killing it");
+                    let _ = child.kill().await;
+                }
+            };
+            if let Some(runtime) = self.runtime.read().unwrap().as_ref() {
+                runtime.block_on(shutdown);
+            }
         }
         if let Some(runtime) = self.runtime.write().unwrap().take() {
             log::info!("Shutting down Tokio runtime...");
@@ -0,0 +1,464 @@
+// 生成したスクリプトから`call_object`に渡される引数のデコード。
+//
+// 値は型を表す1文字のタグで始まり、script_common.luaの`encode_value`と対応する：
+//
+// - `n<数値>;`：数値
+// - `b0` / `b1`：真偽値
+// - `s<バイト数>:<バイト列>`：文字列（長さで区切るのでエスケープは不要）
+// - `_`：nil
+//
+// Vector2などの成分ごとに分かれたパラメーターは、数値を成分の数だけ続けて送る。
+//
+// 並びは`render_params`、パラメーターの宣言（キーと型）、フレームごとの`frame_info`とパラメーターの値の順。
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct LuaRenderParams {
+    pub object_name: String,
+    pub effect_id: i32,
+    pub freeze: bool,
+    pub offline: bool,
+    pub seed: u32,
+    pub error_image: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LuaParameterType {
+    Str,
+    Text,
+    Number,
+    Bool,
+    Color,
+    Select,
+    File,
+    Vector2,
+    Vector3,
+    Range,
+}
+
+impl LuaParameterType {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        Ok(match value {
+            "Str" => Self::Str,
+            "Text" => Self::Text,
+            "Number" => Self::Number,
+            "Bool" => Self::Bool,
+            "Color" => Self::Color,
+            "Select" => Self::Select,
+            "File" => Self::File,
+            "Vector2" => Self::Vector2,
+            "Vector3" => Self::Vector3,
+            "Range" => Self::Range,
+            _ => anyhow::bail!("Unknown parameter type: {}", value),
+        })
+    }
+
+    fn matches(&self, parameter_type: &vi5_cef::ParameterType) -> bool {
+        matches!(
+            (self, parameter_type),
+            (Self::Str, vi5_cef::ParameterType::String)
+                | (Self::Text, vi5_cef::ParameterType::Text)
+                | (Self::Number, vi5_cef::ParameterType::Number { .. })
+                | (Self::Bool, vi5_cef::ParameterType::Boolean)
+                | (Self::Color, vi5_cef::ParameterType::Color)
+                | (Self::Select, vi5_cef::ParameterType::Select { .. })
+                | (Self::File, vi5_cef::ParameterType::File { .. })
+                | (Self::Vector2, vi5_cef::ParameterType::Vector2 { .. })
+                | (Self::Vector3, vi5_cef::ParameterType::Vector3 { .. })
+                | (Self::Range, vi5_cef::ParameterType::Range { .. })
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum LuaParameter {
+    Str(String),
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    Color(u32),
+    // 選択肢の番号
+    Select(usize),
+    // パス。未選択なら空文字列
+    File(String),
+    Vector2(f64, f64),
+    Vector3(f64, f64, f64),
+    // (開始, 終了)
+    Range(f64, f64),
+}
+
+#[derive(Debug)]
+pub struct LuaFrameInfo {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub canvas_width: i32,
+    pub canvas_height: i32,
+    pub current_frame: i32,
+    pub current_time: f64,
+    pub total_frames: i32,
+    pub total_time: f64,
+    pub framerate: f64,
+    pub global_frame: i32,
+    pub global_time: f64,
+}
+
+#[derive(Debug)]
+pub struct LuaFrame {
+    pub frame_info: LuaFrameInfo,
+    pub parameters: HashMap<String, LuaParameter>,
+}
+
+#[derive(Debug)]
+pub struct LuaCall {
+    pub render_params: LuaRenderParams,
+    pub parameter_types: Vec<(String, LuaParameterType)>,
+    pub frames: Vec<LuaFrame>,
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn tag(&mut self) -> anyhow::Result<u8> {
+        let tag = *self
+            .input
+            .get(self.position)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of input"))?;
+        self.position += 1;
+        Ok(tag)
+    }
+
+    fn take_until(&mut self, delimiter: u8) -> anyhow::Result<&'a str> {
+        let rest = &self.input[self.position..];
+        let end = rest
+            .iter()
+            .position(|&b| b == delimiter)
+            .ok_or_else(|| anyhow::anyhow!("Unterminated value at byte {}", self.position))?;
+        self.position += end + 1;
+        Ok(std::str::from_utf8(&rest[..end])?)
+    }
+
+    fn unexpected(&self, tag: u8, expected: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Expected {} at byte {}, found {:?}",
+            expected,
+            self.position - 1,
+            tag as char
+        )
+    }
+
+    fn number(&mut self) -> anyhow::Result<f64> {
+        match self.tag()? {
+            b'n' => {
+                let value = self.take_until(b';')?;
+                let number: f64 = value
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid number {:?}: {}", value, e))?;
+                // Luaの`%.17g`はinf・nanも書けるが、描画には渡せない
+                if !number.is_finite() {
+                    anyhow::bail!("Non-finite number {:?}", value);
+                }
+                Ok(number)
+            }
+            tag => Err(self.unexpected(tag, "a number")),
+        }
+    }
+
+    fn optional_number(&mut self) -> anyhow::Result<Option<f64>> {
+        if self.input.get(self.position) == Some(&b'_') {
+            self.position += 1;
+            return Ok(None);
+        }
+        self.number().map(Some)
+    }
+
+    fn integer(&mut self) -> anyhow::Result<i64> {
+        let value = self.number()?;
+        if value.fract() != 0.0 {
+            anyhow::bail!("Expected an integer, found {}", value);
+        }
+        Ok(value as i64)
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::try_from(self.integer()?)?)
+    }
+
+    fn bool(&mut self) -> anyhow::Result<bool> {
+        match self.tag()? {
+            b'b' => match self.tag()? {
+                b'0' => Ok(false),
+                b'1' => Ok(true),
+                tag => Err(self.unexpected(tag, "0 or 1")),
+            },
+            tag => Err(self.unexpected(tag, "a boolean")),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        match self.tag()? {
+            b's' => {
+                let len: usize = self.take_until(b':')?.parse()?;
+                let bytes = self
+                    .position
+                    .checked_add(len)
+                    .and_then(|end| self.input.get(self.position..end))
+                    .ok_or_else(|| anyhow::anyhow!("String runs past the end of input"))?;
+                self.position += len;
+                Ok(String::from_utf8(bytes.to_vec())?)
+            }
+            tag => Err(self.unexpected(tag, "a string")),
+        }
+    }
+
+    fn frame_info(&mut self) -> anyhow::Result<LuaFrameInfo> {
+        Ok(LuaFrameInfo {
+            x: self.number()?,
+            y: self.number()?,
+            z: self.number()?,
+            canvas_width: self.i32()?,
+            canvas_height: self.i32()?,
+            current_frame: self.i32()?,
+            current_time: self.number()?,
+            total_frames: self.i32()?,
+            total_time: self.number()?,
+            framerate: self.number()?,
+            global_frame: self.i32()?,
+            global_time: self.number()?,
+        })
+    }
+
+    fn parameter(&mut self, parameter_type: LuaParameterType) -> anyhow::Result<LuaParameter> {
+        Ok(match parameter_type {
+            LuaParameterType::Str => LuaParameter::Str(self.string()?),
+            LuaParameterType::Text => LuaParameter::Text(self.string()?),
+            LuaParameterType::Number => LuaParameter::Number(self.number()?),
+            LuaParameterType::Bool => LuaParameter::Bool(self.bool()?),
+            LuaParameterType::Color => {
+                LuaParameter::Color(self.optional_number()?.unwrap_or(0.0) as u32)
+            }
+            LuaParameterType::Select => LuaParameter::Select(usize::try_from(self.integer()?)?),
+            LuaParameterType::File => LuaParameter::File(self.string()?),
+            LuaParameterType::Vector2 => LuaParameter::Vector2(self.number()?, self.number()?),
+            LuaParameterType::Vector3 => {
+                LuaParameter::Vector3(self.number()?, self.number()?, self.number()?)
+            }
+            LuaParameterType::Range => LuaParameter::Range(self.number()?, self.number()?),
+        })
+    }
+}
+
+impl LuaCall {
+    pub fn decode(input: &str) -> anyhow::Result<Self> {
+        let mut reader = Reader {
+            input: input.as_bytes(),
+            position: 0,
+        };
+        let render_params = LuaRenderParams {
+            object_name: reader.string()?,
+            effect_id: reader.i32()?,
+            freeze: reader.bool()?,
+            offline: reader.bool()?,
+            seed: u32::try_from(reader.integer()?)?,
+            error_image: reader.bool()?,
+        };
+
+        let parameter_count = usize::try_from(reader.integer()?)?;
+        let parameter_types = (0..parameter_count)
+            .map(|_| {
+                let key = reader.string()?;
+                let parameter_type = LuaParameterType::parse(&reader.string()?)?;
+                Ok((key, parameter_type))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let frame_count = usize::try_from(reader.integer()?)?;
+        let frames = (0..frame_count)
+            .map(|i| {
+                let frame_info = reader
+                    .frame_info()
+                    .map_err(|e| anyhow::anyhow!("Invalid frame info for frame {}: {}", i, e))?;
+                let parameters = parameter_types
+                    .iter()
+                    .map(|(key, parameter_type)| {
+                        let value = reader.parameter(*parameter_type).map_err(|e| {
+                            anyhow::anyhow!("Invalid value for parameter `{}`: {}", key, e)
+                        })?;
+                        Ok((key.clone(), value))
+                    })
+                    .collect::<anyhow::Result<HashMap<_, _>>>()?;
+                Ok(LuaFrame {
+                    frame_info,
+                    parameters,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if reader.position != input.len() {
+            anyhow::bail!("Trailing data at byte {}", reader.position);
+        }
+        Ok(Self {
+            render_params,
+            parameter_types,
+            frames,
+        })
+    }
+
+    /// スクリプトが送ってきたパラメーターが、オブジェクトの定義と一致しているか確認する。
+    /// 一致しないのはスクリプトが古い場合なので、再生成を促す。
+    pub fn check_schema(&self, object_info: &vi5_cef::ObjectInfo) -> anyhow::Result<()> {
+        let declared = self
+            .parameter_types
+            .iter()
+            .map(|(key, parameter_type)| (key.as_str(), parameter_type))
+            .collect::<HashMap<_, _>>();
+        let mut problems = vec![];
+        for definition in &object_info.parameter_definitions {
+            match declared.get(definition.key.as_str()) {
+                None => problems.push(format!("`{}` is missing", definition.key)),
+                Some(parameter_type) if !parameter_type.matches(&definition.parameter_type) => {
+                    problems.push(format!(
+                        "`{}` is {:?} in the script but {:?} in the object",
+                        definition.key, parameter_type, definition.parameter_type
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, _) in &self.parameter_types {
+            if !object_info
+                .parameter_definitions
+                .iter()
+                .any(|definition| &definition.key == key)
+            {
+                problems.push(format!("`{}` is not defined by the object", key));
+            }
+        }
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "The script for {} does not match the object ({}). Reload the project to regenerate the script.",
+                object_info.id,
+                problems.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 数値のパラメーター`speed`を1つ持つ、1フレーム分の引数
+    fn payload(speed: &str) -> String {
+        format!(
+            "s4:testn1;b0b1n42;b1n1;s5:speeds6:Numbern1;{}{}",
+            "n0;n0;n0;n1920;n1080;n5;n0.5;n60;n2;n30;n105;n3.5;", speed
+        )
+    }
+
+    fn object_info(parameter_type: vi5_cef::ParameterType) -> vi5_cef::ObjectInfo {
+        vi5_cef::ObjectInfo {
+            id: "/src/test.object.ts".to_string(),
+            label: "Test".to_string(),
+            parameter_definitions: vec![vi5_cef::ParameterDefinition {
+                key: "speed".to_string(),
+                parameter_type,
+                label: "Speed".to_string(),
+                default_value: None,
+                group: None,
+                description: None,
+                order: None,
+            }],
+            stateful: false,
+            version: String::new(),
+            dependencies: None,
+            kind: vi5_cef::ObjectKind::Generator,
+        }
+    }
+
+    fn number_type() -> vi5_cef::ParameterType {
+        vi5_cef::ParameterType::Number {
+            step: vi5_cef::NumberStep::One,
+            min: 0.0,
+            max: 100.0,
+        }
+    }
+
+    #[test]
+    fn decodes_a_valid_payload() {
+        let call = LuaCall::decode(&payload("n12.5;")).unwrap();
+        assert_eq!(call.render_params.object_name, "test");
+        assert_eq!(call.render_params.seed, 42);
+        assert!(call.render_params.offline);
+        assert_eq!(call.frames.len(), 1);
+        assert_eq!(call.frames[0].frame_info.global_frame, 105);
+        assert!(matches!(
+            call.frames[0].parameters.get("speed"),
+            Some(LuaParameter::Number(12.5))
+        ));
+        call.check_schema(&object_info(number_type())).unwrap();
+    }
+
+    #[test]
+    fn rejects_truncated_payloads() {
+        let full = payload("n12.5;");
+        for end in 0..full.len() {
+            assert!(LuaCall::decode(&full[..end]).is_err(), "{:?}", &full[..end]);
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        assert!(LuaCall::decode(&payload("n12.5;_")).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_tags() {
+        // 数値の代わりに文字列や真偽値が来る
+        assert!(LuaCall::decode(&payload("s2:12")).is_err());
+        assert!(LuaCall::decode(&payload("b1")).is_err());
+        assert!(LuaCall::decode(&payload("_")).is_err());
+        // 真偽値の値が0/1でない
+        assert!(LuaCall::decode("s4:testn1;b2").is_err());
+        // 未知の型名
+        assert!(LuaCall::decode("s4:testn1;b0b0n0;b1n1;s5:speeds4:Fooon0;").is_err());
+    }
+
+    #[test]
+    fn rejects_overrunning_length_prefixes() {
+        assert!(LuaCall::decode("s5:test").is_err());
+        assert!(LuaCall::decode(&format!("s{}:test", usize::MAX)).is_err());
+        assert!(LuaCall::decode("s99999999999999999999999:test").is_err());
+        assert!(LuaCall::decode("s-1:test").is_err());
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        for value in ["inf", "-inf", "nan", "-nan", "1e999"] {
+            assert!(
+                LuaCall::decode(&payload(&format!("n{};", value))).is_err(),
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn reports_schema_type_mismatches() {
+        let call = LuaCall::decode(&payload("n12.5;")).unwrap();
+        let error = call
+            .check_schema(&object_info(vi5_cef::ParameterType::Boolean))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`speed` is Number"), "{}", error);
+
+        // フレームがなくても、宣言された型で確認する
+        let call = LuaCall::decode("s4:testn1;b0b0n0;b1n1;s5:speeds5:Colorn0;").unwrap();
+        assert!(call.check_schema(&object_info(number_type())).is_err());
+    }
+}
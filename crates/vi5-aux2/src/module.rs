@@ -1,67 +1,55 @@
 use std::{
     collections::HashMap,
     hash::{Hash, Hasher},
+    sync::Arc,
 };
 
 use aviutl2::{AnyResult, AviUtl2Info, generic::GenericPlugin, log, module::ScriptModuleFunctions};
 
 use crate::Vi5Aux2;
+use crate::batch_size::BatchSizeController;
+use crate::error_image::{ErrorCode, RenderFailure};
+use crate::lua_call::{LuaCall, LuaFrameInfo, LuaParameter};
+use crate::render_cache::{RenderCache, RenderCacheEntry};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
-struct LuaRenderParams {
-    object_name: String,
-    effect_id: i32,
-    freeze: bool,
-    offline: bool,
-}
-
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
-#[serde(tag = "type", content = "value")]
-enum LuaParameter {
-    Str(String),
-    Text(String),
-    Number(f64),
-    Bool(bool),
-    Color(u32),
-}
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
-struct LuaFrameInfo {
-    x: f64,
-    y: f64,
-    z: f64,
-    canvas_width: i32,
-    canvas_height: i32,
-    current_frame: i32,
-    current_time: f64,
-    total_frames: i32,
-    total_time: f64,
-    framerate: f64,
-    global_frame: i32,
-    global_time: f64,
-}
+// まだ一度も描画できていないときのエラー画像の大きさ
+const DEFAULT_ERROR_IMAGE_SIZE: (usize, usize) = (640, 360);
+// AviUtl2で扱える画像の最大の幅・高さ
+const MAX_INPUT_IMAGE_SIDE: usize = 8192;
+pub(crate) const RENDER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[aviutl2::plugin(ScriptModule)]
 pub struct InternalModule;
 
 static TEMPORARY_BUFFER: std::sync::LazyLock<dashmap::DashMap<i32, Vec<u8>>> =
     std::sync::LazyLock::new(dashmap::DashMap::new);
-static RENDER_CACHE: std::sync::LazyLock<dashmap::DashMap<i32, RenderCachePerEffectEntry>> =
+static RENDER_CACHE: std::sync::LazyLock<RenderCache> =
+    std::sync::LazyLock::new(|| RenderCache::new(crate::settings::get().render_cache_budget()));
+static BATCH_SIZE: std::sync::LazyLock<BatchSizeController> =
+    std::sync::LazyLock::new(BatchSizeController::default);
+static IS_FROZEN: std::sync::LazyLock<dashmap::DashMap<i32, bool>> =
+    std::sync::LazyLock::new(dashmap::DashMap::new);
+static PENDING_RENDERS: std::sync::LazyLock<dashmap::DashMap<i32, ()>> =
     std::sync::LazyLock::new(dashmap::DashMap::new);
-static ADJUSTED_BATCH_SIZE: std::sync::LazyLock<dashmap::DashMap<i32, usize>> =
+static LAST_IMAGES: std::sync::LazyLock<dashmap::DashMap<i32, Arc<RenderCacheEntry>>> =
     std::sync::LazyLock::new(dashmap::DashMap::new);
-static IS_FROZEN: std::sync::LazyLock<dashmap::DashMap<i32, bool>> =
+// バックグラウンドの描画に失敗し、エラー画像を出しているキャッシュキー
+static FAILED_RENDERS: std::sync::LazyLock<dashmap::DashMap<i32, u64>> =
+    std::sync::LazyLock::new(dashmap::DashMap::new);
+// 一度も描画できていないときに返す、透明な1x1の画像
+static PLACEHOLDER_IMAGE: std::sync::LazyLock<Arc<RenderCacheEntry>> =
+    std::sync::LazyLock::new(|| {
+        Arc::new(RenderCacheEntry {
+            image_data: vec![0; 4],
+            width: 1,
+            height: 1,
+        })
+    });
+static OBJECT_INFOS: std::sync::LazyLock<dashmap::DashMap<String, vi5_cef::ObjectInfo>> =
+    std::sync::LazyLock::new(dashmap::DashMap::new);
+// フィルタースクリプトが`call_object`の前に渡してくる、適用先のオブジェクトの画像
+static INPUT_IMAGES: std::sync::LazyLock<dashmap::DashMap<i32, vi5_cef::InputImage>> =
     std::sync::LazyLock::new(dashmap::DashMap::new);
-
-#[derive(Debug, Clone, Default)]
-struct RenderCachePerEffectEntry {
-    images: HashMap<u64, RenderCacheEntry>,
-}
-#[derive(Debug, Clone)]
-struct RenderCacheEntry {
-    image_data: Vec<u8>,
-    width: usize,
-    height: usize,
-}
 
 fn hash_parameter_value(param: &vi5_cef::ParameterValue, hasher: &mut impl Hasher) {
     match param {
@@ -88,13 +76,80 @@ fn hash_parameter_value(param: &vi5_cef::ParameterValue, hasher: &mut impl Hashe
             value.b.hash(hasher);
             value.a.hash(hasher);
         }
+        vi5_cef::ParameterValue::Select(value) => {
+            5_u8.hash(hasher);
+            value.hash(hasher);
+        }
+        vi5_cef::ParameterValue::File(value) => {
+            6_u8.hash(hasher);
+            // ファイルが編集されたら描画し直す
+            value.path.hash(hasher);
+            value.modified_ms.hash(hasher);
+        }
+        vi5_cef::ParameterValue::Vector2 { x, y } => {
+            7_u8.hash(hasher);
+            x.to_bits().hash(hasher);
+            y.to_bits().hash(hasher);
+        }
+        vi5_cef::ParameterValue::Vector3 { x, y, z } => {
+            8_u8.hash(hasher);
+            x.to_bits().hash(hasher);
+            y.to_bits().hash(hasher);
+            z.to_bits().hash(hasher);
+        }
+        vi5_cef::ParameterValue::Range { start, end } => {
+            9_u8.hash(hasher);
+            start.to_bits().hash(hasher);
+            end.to_bits().hash(hasher);
+        }
+    }
+}
+
+/// オブジェクトが依存しないと宣言した入力を固定値に置き換えたリクエストを返す。
+/// キャッシュキーはこれから作るので、依存しない入力が変わっても同じ画像が使われる。
+pub(crate) fn strip_independent_inputs(
+    request: &vi5_cef::RenderRequest,
+) -> std::borrow::Cow<'_, vi5_cef::RenderRequest> {
+    let Some((dependencies, stateful)) = OBJECT_INFOS.get(&request.object).and_then(|info| {
+        info.dependencies
+            .map(|dependencies| (dependencies, info.stateful))
+    }) else {
+        return std::borrow::Cow::Borrowed(request);
+    };
+    let mut request = request.clone();
+    let frame_info = &mut request.frame_info;
+    // 状態を持つオブジェクトはフレームごとに結果が変わる
+    if !dependencies.time && !stateful {
+        frame_info.current_frame = 0;
+        frame_info.current_time = 0.0;
+        frame_info.total_frames = 0;
+        frame_info.total_time = 0.0;
+        frame_info.framerate = 0.0;
+        frame_info.global_frame = 0;
+        frame_info.global_time = 0.0;
+    }
+    if !dependencies.position {
+        frame_info.x = 0.0;
+        frame_info.y = 0.0;
+        frame_info.z = 0.0;
+    }
+    if !dependencies.screen_size {
+        frame_info.screen_width = 0;
+        frame_info.screen_height = 0;
+    }
+    if !dependencies.parameters {
+        request.parameters.clear();
     }
+    std::borrow::Cow::Owned(request)
 }
 
-fn compute_cache_key(request: &vi5_cef::RenderRequest) -> u64 {
+pub(crate) fn compute_cache_key(request: &vi5_cef::RenderRequest) -> u64 {
+    let request = strip_independent_inputs(request);
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     request.object.hash(&mut hasher);
     request.object_id.hash(&mut hasher);
+    request.is_offline.hash(&mut hasher);
+    request.seed.hash(&mut hasher);
     for param in &request.parameters {
         param.key.hash(&mut hasher);
         hash_parameter_value(&param.value, &mut hasher);
@@ -111,15 +166,22 @@ fn compute_cache_key(request: &vi5_cef::RenderRequest) -> u64 {
     request.frame_info.framerate.to_bits().hash(&mut hasher);
     request.frame_info.global_frame.hash(&mut hasher);
     request.frame_info.global_time.to_bits().hash(&mut hasher);
+    if let Some(input_image) = &request.input_image {
+        input_image.width.hash(&mut hasher);
+        input_image.height.hash(&mut hasher);
+        input_image.image_data.hash(&mut hasher);
+    }
     hasher.finish()
 }
 
 fn build_render_request(
     object_name: String,
+    object_info: Option<&vi5_cef::ObjectInfo>,
     effect_id: i32,
     params: &HashMap<String, LuaParameter>,
     frame_info: &LuaFrameInfo,
     is_offline: bool,
+    seed: u32,
 ) -> anyhow::Result<vi5_cef::RenderRequest> {
     let mut param_keys: Vec<&String> = params.keys().collect();
     param_keys.sort();
@@ -136,6 +198,16 @@ fn build_render_request(
                     LuaParameter::Text(v) => vi5_cef::ParameterValue::Text(v.clone()),
                     LuaParameter::Number(v) => vi5_cef::ParameterValue::Number(*v),
                     LuaParameter::Bool(v) => vi5_cef::ParameterValue::Bool(*v),
+                    LuaParameter::File(v) => {
+                        vi5_cef::ParameterValue::File(vi5_cef::FileValue::from_path(v.clone()))
+                    }
+                    &LuaParameter::Vector2(x, y) => vi5_cef::ParameterValue::Vector2 { x, y },
+                    &LuaParameter::Vector3(x, y, z) => vi5_cef::ParameterValue::Vector3 { x, y, z },
+                    // トラックバーは別々に動かせるので、逆転していたら入れ替える
+                    &LuaParameter::Range(start, end) => vi5_cef::ParameterValue::Range {
+                        start: start.min(end),
+                        end: start.max(end),
+                    },
                     LuaParameter::Color(v) => {
                         let color = *v;
                         vi5_cef::ParameterValue::Color(vi5_cef::Color {
@@ -145,6 +217,30 @@ fn build_render_request(
                             a: ((color >> 24) & 0xFF) as u8,
                         })
                     }
+                    LuaParameter::Select(index) => {
+                        let options = object_info
+                            .and_then(|info| {
+                                info.parameter_definitions
+                                    .iter()
+                                    .find(|definition| &definition.key == key)
+                            })
+                            .and_then(|definition| match &definition.parameter_type {
+                                vi5_cef::ParameterType::Select { options } => Some(options),
+                                _ => None,
+                            })
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("Unknown options for select parameter: {}", key)
+                            })?;
+                        let (_, value) = options.get(*index).ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Option {} is out of range for parameter {} ({} options)",
+                                index,
+                                key,
+                                options.len()
+                            )
+                        })?;
+                        vi5_cef::ParameterValue::Select(value.clone())
+                    }
                 },
             })
         })
@@ -168,6 +264,8 @@ fn build_render_request(
         },
         parameters,
         is_offline,
+        seed,
+        input_image: None,
     })
 }
 
@@ -184,225 +282,396 @@ impl aviutl2::module::ScriptModule for InternalModule {
     }
 }
 
-#[aviutl2::module::functions]
-impl InternalModule {
-    fn get_batch_size(&self, effect_id: i32) -> usize {
-        *ADJUSTED_BATCH_SIZE.entry(effect_id).or_insert(1).value()
-    }
-
-    fn call_object(
-        &self,
-        render_params: String,
-        params_json: String,
-        frame_info_json: String,
-    ) -> aviutl2::AnyResult<(*const u8, usize, usize)> {
-        let render_params: LuaRenderParams = serde_json::from_str(&render_params)?;
-        let batch_params: Vec<HashMap<String, LuaParameter>> = serde_json::from_str(&params_json)?;
-        let batch_frame_info: Vec<LuaFrameInfo> = serde_json::from_str(&frame_info_json)?;
-        let batch_render_request = if batch_params.len() != batch_frame_info.len() {
-            anyhow::bail!(
-                "Mismatched batch sizes: params {}, frame_info {}",
-                batch_params.len(),
-                batch_frame_info.len()
-            );
-        } else {
-            batch_params
-                .into_iter()
-                .zip(batch_frame_info.into_iter())
-                .map(|(params, frame_info)| {
-                    build_render_request(
-                        render_params.object_name.clone(),
-                        render_params.effect_id,
-                        &params,
-                        &frame_info,
-                        render_params.offline,
-                    )
-                })
-                .collect::<anyhow::Result<Vec<vi5_cef::RenderRequest>>>()?
+/// 描画してメモリ（とディスク）のキャッシュに入れ、キャッシュキーごとの結果を返す。
+pub(crate) async fn render_and_store(
+    server: crate::Vi5Server,
+    effect_id: i32,
+    requests: Vec<(u64, Option<String>, vi5_cef::RenderRequest)>,
+    disk_cache: Option<Arc<crate::disk_cache::DiskCache>>,
+) -> anyhow::Result<HashMap<u64, Result<Arc<RenderCacheEntry>, String>>> {
+    let (keys, requests) = requests
+        .into_iter()
+        .map(|(cache_key, disk_key, request)| ((cache_key, disk_key), request))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    let request_count = requests.len();
+    let (rendered, render_elapsed) = {
+        let mut server_guard = server.lock().await;
+        // 先読みや他のエフェクトの描画を待っていた時間は含めない
+        let render_started = std::time::Instant::now();
+        let Some((_, client, _)) = server_guard.as_mut() else {
+            return Err(RenderFailure::new(
+                ErrorCode::ServerUnavailable,
+                "vi5-cef server is not running",
+            )
+            .into());
         };
+        let rendered = tokio::time::timeout(RENDER_TIMEOUT, client.batch_render(requests))
+            .await
+            .map_err(|_| RenderFailure::new(ErrorCode::Timeout, "Batch render timed out"))?
+            .map_err(|e| {
+                RenderFailure::new(
+                    ErrorCode::ServerUnavailable,
+                    format!("Batch render failed: {}", e),
+                )
+            })?;
+        (rendered, render_started.elapsed())
+    };
 
-        let mut current_freeze_state = IS_FROZEN
-            .entry(render_params.effect_id)
-            .or_insert(render_params.freeze);
-        let cache_dir = dirs::cache_dir()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get cache directory"))?
-            .join("vi5_aux2_cache")
-            .join(render_params.effect_id.to_string());
-        match (render_params.freeze, *current_freeze_state) {
-            (true, false) => {
-                log::info!("Freezing cache for effect_id {}", render_params.effect_id);
-                *current_freeze_state = true;
+    let mut results = HashMap::new();
+    let mut largest_size = (0, 0);
+    for (response, (cache_key, disk_key)) in rendered.into_iter().zip(keys) {
+        match response.response {
+            vi5_cef::RenderResponseData::Success {
+                width,
+                height,
+                mut image_data,
+                alpha_mode,
+                color_space,
+            } => {
+                // putpixeldataはアルファ非乗算のsRGBを受け取るので、キャッシュする前に変換しておく
+                vi5_cef::to_straight_srgb(&mut image_data, alpha_mode, color_space);
+                if width > largest_size.0 {
+                    largest_size.0 = width;
+                }
+                if height > largest_size.1 {
+                    largest_size.1 = height;
+                }
+                let entry = RENDER_CACHE.insert(
+                    effect_id,
+                    cache_key,
+                    RenderCacheEntry {
+                        image_data,
+                        width: width as usize,
+                        height: height as usize,
+                    },
+                );
+
+                if let Some(disk_cache) = &disk_cache
+                    && let Some(disk_key) = &disk_key
+                    && let Err(e) = disk_cache.insert(disk_key, &entry)
+                {
+                    log::error!("Failed to save image {} to disk cache: {}", disk_key, e);
+                }
+                results.insert(cache_key, Ok(entry));
             }
-            (false, true) => {
-                log::info!("Unfreezing cache for effect_id {}", render_params.effect_id);
-                std::fs::remove_dir_all(&cache_dir).ok();
-                *current_freeze_state = false;
+            vi5_cef::RenderResponseData::Error(err) => {
+                results.insert(cache_key, Err(err));
             }
-            _ => {}
         }
+    }
 
-        let batch_cache_keys: Vec<u64> =
-            batch_render_request.iter().map(compute_cache_key).collect();
+    // 全部失敗したときは画像の大きさが分からないので、計測値に入れない
+    if results.values().any(Result::is_ok) {
+        BATCH_SIZE.record(
+            effect_id,
+            request_count,
+            render_elapsed,
+            (largest_size.0 as u32, largest_size.1 as u32),
+        );
+    }
+    Ok(results)
+}
 
-        if *current_freeze_state {
-            log::debug!(
-                "Cache is frozen for effect_id {}, skipping rendering and using existing cache if available",
-                render_params.effect_id
-            );
-            let cache_path = cache_dir.join(format!("{}.webp", batch_cache_keys[0]));
-            if let Ok(opened) = image::open(&cache_path).map(|img| img.into_rgba8()) {
-                log::debug!(
-                    "Loaded cached image from {:?} for effect_id {}",
-                    cache_path,
-                    render_params.effect_id
-                );
-                let (width, height) = opened.dimensions();
-                let image_data = opened.into_raw();
-                TEMPORARY_BUFFER.insert(render_params.effect_id, image_data.clone());
-                return Ok((image_data.as_ptr(), width as usize, height as usize));
+fn render_current(call: &LuaCall) -> anyhow::Result<Arc<RenderCacheEntry>> {
+    let render_params = &call.render_params;
+    let object_info = OBJECT_INFOS
+        .get(&render_params.object_name)
+        .map(|info| info.clone());
+    if let Some(object_info) = &object_info {
+        call.check_schema(object_info)?;
+    }
+    let mut batch_render_request = call
+        .frames
+        .iter()
+        .map(|frame| {
+            build_render_request(
+                render_params.object_name.clone(),
+                object_info.as_ref(),
+                render_params.effect_id,
+                &frame.parameters,
+                &frame.frame_info,
+                render_params.offline,
+                render_params.seed,
+            )
+        })
+        .collect::<anyhow::Result<Vec<vi5_cef::RenderRequest>>>()?;
+    match INPUT_IMAGES.remove(&render_params.effect_id) {
+        Some((_, input_image)) => {
+            // 入力画像は現在のフレームのものしか分からないので、まとめて描画しない
+            batch_render_request.truncate(1);
+            if let Some(request) = batch_render_request.first_mut() {
+                request.input_image = Some(input_image);
             }
         }
+        None if object_info
+            .as_ref()
+            .is_some_and(|info| info.kind == vi5_cef::ObjectKind::Filter) =>
+        {
+            anyhow::bail!(
+                "{} is a filter but the script did not send the input image. Reload the project to regenerate the script.",
+                render_params.object_name
+            );
+        }
+        None => {}
+    }
 
-        let mut cached_entries = RENDER_CACHE.entry(render_params.effect_id).or_default();
+    let mut current_freeze_state = IS_FROZEN
+        .entry(render_params.effect_id)
+        .or_insert(render_params.freeze);
+    match (render_params.freeze, *current_freeze_state) {
+        (true, false) => {
+            log::info!("Freezing cache for effect_id {}", render_params.effect_id);
+            *current_freeze_state = true;
+        }
+        (false, true) => {
+            log::info!("Unfreezing cache for effect_id {}", render_params.effect_id);
+            *current_freeze_state = false;
+        }
+        _ => {}
+    }
 
-        let should_render_now = !cached_entries.images.contains_key(&batch_cache_keys[0]);
+    let batch_cache_keys: Vec<u64> = batch_render_request.iter().map(compute_cache_key).collect();
+    let prefetch_template = batch_render_request
+        .last()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Empty batch"))?;
+    let served_frame = batch_render_request[0].frame_info.current_frame;
 
-        if should_render_now {
-            let (uncached_keys, uncached_requests) = batch_render_request
-                .into_iter()
-                .enumerate()
-                .filter_map(|(i, req)| {
-                    if !cached_entries.images.contains_key(&batch_cache_keys[i]) {
-                        Some((batch_cache_keys[i], req))
-                    } else {
-                        None
-                    }
-                })
-                .unzip::<_, _, Vec<_>, Vec<_>>();
-            log::debug!(
-                "Rendering {} uncached requests for effect_id {}",
-                uncached_requests.len(),
-                render_params.effect_id
-            );
-            let rendered = Vi5Aux2::with_instance({
-                move |instance| {
-                    instance
-                        .runtime
-                        .read()
-                        .map_err(|e| {
-                            anyhow::anyhow!("Failed to acquire runtime read lock: {}", e)
-                        })?
-                        .as_ref()
-                        .ok_or_else(|| {
-                            anyhow::anyhow!("tokio runtime is not initialized")
-                        })?
-                        .block_on(instance.with_client(async move |client| {
-                        tokio::select! {
-                            result = client
-                                .batch_render(uncached_requests) => {
-                                    result.map_err(|e| anyhow::anyhow!("Batch render failed: {}", e))
-                                }
-                            _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {
-                                Err(anyhow::anyhow!("Batch render timed out"))
-                            }
-                        }
-                    }))
-                }
-            })?;
-            // batch_cache_keys に存在しないキャッシュを削除
-            cached_entries
-                .images
-                .retain(|key, _| batch_cache_keys.contains(key));
+    // ソースのバージョンが分からないオブジェクトは、内容が変わっても気づけないのでディスクに残さない
+    let disk_cache = crate::disk_cache::current()
+        .filter(|_| *current_freeze_state)
+        .zip(
+            object_info
+                .as_ref()
+                .map(|info| info.version.clone())
+                .filter(|version| !version.is_empty()),
+        )
+        .map(|(disk_cache, version)| {
+            let keys = batch_render_request
+                .iter()
+                .map(|request| crate::disk_cache::cache_key(request, &version))
+                .collect::<Vec<_>>();
+            (disk_cache, keys)
+        });
 
-            let mut largest_size = (0, 0);
-            for (response, cache_key) in rendered.into_iter().zip(uncached_keys.into_iter()) {
-                match response.response {
-                    vi5_cef::RenderResponseData::Success {
-                        width,
-                        height,
-                        image_data,
-                    } => {
-                        if width > largest_size.0 {
-                            largest_size.0 = width;
-                        }
-                        if height > largest_size.1 {
-                            largest_size.1 = height;
-                        }
-                        cached_entries.images.insert(
-                            cache_key,
-                            RenderCacheEntry {
-                                image_data,
-                                width: width as usize,
-                                height: height as usize,
-                            },
-                        );
+    let mut current_image = RENDER_CACHE.get(render_params.effect_id, batch_cache_keys[0]);
+    if current_image.is_none()
+        && let Some((disk_cache, disk_keys)) = &disk_cache
+        && let Some(entry) = disk_cache.get(&disk_keys[0])
+    {
+        log::debug!(
+            "Loaded image {} from disk cache for effect_id {}",
+            disk_keys[0],
+            render_params.effect_id
+        );
+        current_image =
+            Some(RENDER_CACHE.insert(render_params.effect_id, batch_cache_keys[0], entry));
+    }
 
-                        if *current_freeze_state {
-                            let cache_path = cache_dir.join(format!("{}.webp", cache_key));
-                            if let Err(e) = std::fs::create_dir_all(&cache_dir)
-                                .map_err(anyhow::Error::from)
-                                .and_then(|_| {
-                                    image::RgbaImage::from_raw(
-                                        width as _,
-                                        height as _,
-                                        cached_entries
-                                            .images
-                                            .get(&cache_key)
-                                            .unwrap()
-                                            .image_data
-                                            .clone(),
-                                    )
-                                    .ok_or_else(|| {
-                                        anyhow::anyhow!("Failed to create image from raw data")
-                                    })
-                                    .and_then(|img| {
-                                        img.save_with_format(&cache_path, image::ImageFormat::WebP)
-                                            .map_err(|e| {
-                                                anyhow::anyhow!(
-                                                    "Failed to save cached image to {:?}: {}",
-                                                    cache_path,
-                                                    e
-                                                )
-                                            })
-                                    })
-                                })
-                            {
-                                log::error!(
-                                    "Failed to save cached image for cache_key {}: {}",
-                                    cache_key,
-                                    e
-                                );
-                            } else {
-                                log::debug!(
-                                    "Saved cached image to {:?} for cache_key {}",
-                                    cache_path,
-                                    cache_key
-                                );
-                            }
+    if current_image.is_none() {
+        let (disk_cache, disk_keys) = match disk_cache {
+            Some((disk_cache, keys)) => (Some(disk_cache), keys.into_iter().map(Some).collect()),
+            None => (None, vec![None; batch_render_request.len()]),
+        };
+        // 依存しない入力しか違わないフレームは同じキーになるので、1回だけ描画する
+        let mut seen_keys = std::collections::HashSet::new();
+        let uncached = batch_render_request
+            .into_iter()
+            .zip(batch_cache_keys.iter().copied())
+            .zip(disk_keys)
+            .filter(|((_, cache_key), _)| {
+                seen_keys.insert(*cache_key)
+                    && !RENDER_CACHE.contains(render_params.effect_id, *cache_key)
+            })
+            .map(|((request, cache_key), disk_key)| (cache_key, disk_key, request))
+            .collect::<Vec<_>>();
+        log::debug!(
+            "Rendering {} uncached requests for effect_id {}",
+            uncached.len(),
+            render_params.effect_id
+        );
+        let (runtime_handle, server) = Vi5Aux2::with_instance(|instance| {
+            let runtime = instance
+                .runtime
+                .read()
+                .map_err(|e| anyhow::anyhow!("Failed to acquire runtime read lock: {}", e))?;
+            let runtime = runtime
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("tokio runtime is not initialized"))?;
+            anyhow::Ok((runtime.handle().clone(), instance.server.clone()))
+        })?;
+        let render = render_and_store(server, render_params.effect_id, uncached, disk_cache);
+
+        if !render_params.offline && crate::settings::get().non_blocking_preview {
+            // 描画を待たずに前回の画像を返し、描画が終わったら再描画してもらう
+            let effect_id = render_params.effect_id;
+            let cache_key = batch_cache_keys[0];
+            let object_name = render_params.object_name.clone();
+            let error_image = render_params.error_image;
+            // 失敗したまま再描画すると、また描画が走って無限に繰り返してしまうので、
+            // 同じキーで失敗済みなら描き直さずにエラー画像を返す
+            let already_failed = FAILED_RENDERS
+                .get(&effect_id)
+                .is_some_and(|failed_key| *failed_key == cache_key);
+            if !already_failed && PENDING_RENDERS.insert(effect_id, ()).is_none() {
+                runtime_handle.spawn(async move {
+                    let rendered = render.await;
+                    PENDING_RENDERS.remove(&effect_id);
+                    let error = match rendered.map(|mut rendered| rendered.remove(&cache_key)) {
+                        Ok(Some(Ok(_))) => {
+                            FAILED_RENDERS.remove(&effect_id);
+                            crate::request_redraw();
+                            return;
                         }
-                    }
-                    vi5_cef::RenderResponseData::Error(err) => {
-                        if cache_key == batch_cache_keys[0] {
-                            anyhow::bail!("JS returned error: {}", err);
+                        Ok(Some(Err(err))) => {
+                            log::warn!("JS returned error for effect_id {}: {}", effect_id, err);
+                            anyhow::Error::from(RenderFailure::new(ErrorCode::Script, err))
+                        }
+                        Ok(None) => return,
+                        Err(e) => {
+                            log::warn!(
+                                "Background render failed for effect_id {}: {}",
+                                effect_id,
+                                e
+                            );
+                            e
                         }
-                        continue;
+                    };
+                    if error_image {
+                        let (width, height) = LAST_IMAGES
+                            .get(&effect_id)
+                            .map_or(DEFAULT_ERROR_IMAGE_SIZE, |image| {
+                                (image.width, image.height)
+                            });
+                        LAST_IMAGES.insert(
+                            effect_id,
+                            Arc::new(crate::error_image::render(
+                                &object_name,
+                                &error,
+                                width,
+                                height,
+                            )),
+                        );
+                        FAILED_RENDERS.insert(effect_id, cache_key);
+                        crate::request_redraw();
                     }
+                });
+            }
+            current_image = Some(
+                LAST_IMAGES
+                    .get(&effect_id)
+                    .map(|image| image.clone())
+                    .unwrap_or_else(|| PLACEHOLDER_IMAGE.clone()),
+            );
+        } else {
+            let mut rendered = runtime_handle.block_on(render)?;
+            match rendered.remove(&batch_cache_keys[0]) {
+                Some(Ok(entry)) => current_image = Some(entry),
+                Some(Err(err)) => {
+                    return Err(RenderFailure::new(ErrorCode::Script, err).into());
                 }
+                None => {}
             }
+        }
+    }
 
-            const MAX_BATCH_SIZE: usize = 50;
-            ADJUSTED_BATCH_SIZE.insert(
-                render_params.effect_id,
-                (((2048 / largest_size.0.max(1) / 2) * (2040 / largest_size.1.max(1) / 2))
-                    as usize)
-                    .clamp(1, MAX_BATCH_SIZE),
+    let current_image =
+        current_image.ok_or_else(|| anyhow::anyhow!("Unreachable: first image not rendered"))?;
+    if crate::prefetch::should_prefetch(&prefetch_template, object_info.as_ref()) {
+        crate::prefetch::schedule(
+            &RENDER_CACHE,
+            render_params.effect_id,
+            &prefetch_template,
+            served_frame,
+            // 先読みはサーバーを占有するので、プレビューの遅延目標に合わせる
+            BATCH_SIZE.batch_size(render_params.effect_id, false),
+        );
+    }
+    if !Arc::ptr_eq(&current_image, &PLACEHOLDER_IMAGE) {
+        LAST_IMAGES.insert(render_params.effect_id, current_image.clone());
+    }
+    Ok(current_image)
+}
+
+#[aviutl2::module::functions]
+impl InternalModule {
+    fn get_batch_size(&self, effect_id: i32, is_offline: bool) -> usize {
+        BATCH_SIZE.batch_size(effect_id, is_offline)
+    }
+
+    fn set_input_image(
+        &self,
+        effect_id: i32,
+        image_data: *const u8,
+        width: usize,
+        height: usize,
+    ) -> aviutl2::AnyResult<()> {
+        if image_data.is_null() || width == 0 || height == 0 {
+            anyhow::bail!("No input image for effect_id {}", effect_id);
+        }
+        if width > MAX_INPUT_IMAGE_SIDE || height > MAX_INPUT_IMAGE_SIDE {
+            anyhow::bail!(
+                "Input image for effect_id {} is too large: {}x{}",
+                effect_id,
+                width,
+                height
             );
         }
+        let len = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| anyhow::anyhow!("Input image size overflows: {}x{}", width, height))?;
+        // SAFETY: この関数はscript_filter.luaから、obj.getpixeldata("object", "rgba")の戻り値を
+        // そのまま渡して呼ばれることを前提にしている。そのバッファはRGBAでwidth * height * 4バイトあり、
+        // この関数から戻るまでAviUtl2が保持している。他のスクリプトから任意の値で呼ばれた場合は保証できない
+        let image_data = unsafe { std::slice::from_raw_parts(image_data, len) };
+        INPUT_IMAGES.insert(
+            effect_id,
+            vi5_cef::InputImage {
+                width,
+                height,
+                image_data: image_data.to_vec(),
+            },
+        );
+        Ok(())
+    }
 
-        let current_image = cached_entries
-            .images
-            .get(&batch_cache_keys[0])
-            .ok_or_else(|| anyhow::anyhow!("Unreachable: first image not cached"))?;
+    fn call_object(&self, payload: String) -> aviutl2::AnyResult<(*const u8, usize, usize)> {
+        let call = LuaCall::decode(&payload)
+            .map_err(|e| anyhow::anyhow!("Invalid arguments from script: {}", e))?;
+        let render_params = &call.render_params;
+        let current_image = match render_current(&call) {
+            Ok(image) => image,
+            // 書き出し中にエラー画像を返すと、そのまま動画に入ってしまうので失敗させる
+            Err(e) if render_params.error_image && !render_params.offline => {
+                log::error!(
+                    "Failed to render {} (effect_id {}): {:#}",
+                    render_params.object_name,
+                    render_params.effect_id,
+                    e
+                );
+                let (width, height) = LAST_IMAGES
+                    .get(&render_params.effect_id)
+                    .map_or(DEFAULT_ERROR_IMAGE_SIZE, |image| {
+                        (image.width, image.height)
+                    });
+                Arc::new(crate::error_image::render(
+                    &render_params.object_name,
+                    &e,
+                    width,
+                    height,
+                ))
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to render {} (effect_id {}): {:#}",
+                    render_params.object_name,
+                    render_params.effect_id,
+                    e
+                );
+                return Err(e);
+            }
+        };
         let current_image_data = current_image.image_data.clone();
         let current_image_ptr = current_image_data.as_ptr();
         TEMPORARY_BUFFER.insert(render_params.effect_id, current_image_data);
@@ -417,7 +686,23 @@ impl InternalModule {
     }
 }
 
+pub fn set_object_infos(object_infos: &[vi5_cef::ObjectInfo]) {
+    OBJECT_INFOS.clear();
+    for object_info in object_infos {
+        OBJECT_INFOS.insert(object_info.id.clone(), object_info.clone());
+    }
+}
+
+pub fn set_render_cache_budget(budget: usize) {
+    RENDER_CACHE.set_budget(budget);
+}
+
 pub fn clear_render_cache() {
+    crate::prefetch::stop_all();
+    BATCH_SIZE.clear();
+    LAST_IMAGES.clear();
+    FAILED_RENDERS.clear();
+    INPUT_IMAGES.clear();
     RENDER_CACHE.clear();
     TEMPORARY_BUFFER.clear();
 }
@@ -0,0 +1,271 @@
+use aviutl2::{generic::GenericPlugin, log};
+
+use crate::Vi5Aux2;
+use crate::render_cache::RenderCache;
+
+struct PrefetchState {
+    signature: u64,
+    last_frame: usize,
+    // 先読みを依頼した最後のフレーム
+    end: usize,
+    task: Option<tokio::task::AbortHandle>,
+}
+
+impl PrefetchState {
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+static STATES: std::sync::LazyLock<dashmap::DashMap<i32, PrefetchState>> =
+    std::sync::LazyLock::new(dashmap::DashMap::new);
+
+/// フレームに依存しない部分（パラメーターや位置など）のハッシュ。
+fn template_signature(request: &vi5_cef::RenderRequest) -> u64 {
+    let mut template = request.clone();
+    template.frame_info.current_frame = 0;
+    template.frame_info.current_time = 0.0;
+    template.frame_info.global_frame = 0;
+    template.frame_info.global_time = 0.0;
+    crate::module::compute_cache_key(&template)
+}
+
+fn request_at_frame(template: &vi5_cef::RenderRequest, frame: usize) -> vi5_cef::RenderRequest {
+    let mut request = template.clone();
+    let frame_info = &mut request.frame_info;
+    let offset = frame as i64 - frame_info.current_frame as i64;
+    let time_offset = offset as f64 / frame_info.framerate;
+    frame_info.current_frame = frame;
+    frame_info.current_time += time_offset;
+    frame_info.global_frame = (frame_info.global_frame as i64 + offset).max(0) as usize;
+    frame_info.global_time += time_offset;
+    request
+}
+
+/// 先読みしてよいリクエストか。
+///
+/// - フィルターは先のフレームの入力画像が分からない
+/// - 状態を持つオブジェクトは、先のフレームを描くと表示中のフレームへの巻き戻しになってしまう
+/// - 書き出し中は、先読みが書き出しと同じサーバーを取り合ってしまう
+pub fn should_prefetch(
+    request: &vi5_cef::RenderRequest,
+    object_info: Option<&vi5_cef::ObjectInfo>,
+) -> bool {
+    request.input_image.is_none()
+        && !request.is_offline
+        && !object_info.is_some_and(|info| info.stateful)
+}
+
+/// `template`を元に、`served_frame`より後のフレームをバックグラウンドで描画してキャッシュに入れる。
+///
+/// シークやパラメーターの変更を検知したら、実行中の先読みを止める。
+/// パラメーターが毎フレーム変わる（トラックバーが動いている）場合は、
+/// Rust側で値を補間できないので先読みしない。
+pub fn schedule(
+    cache: &'static RenderCache,
+    effect_id: i32,
+    template: &vi5_cef::RenderRequest,
+    served_frame: usize,
+    batch_size: usize,
+) {
+    let lookahead = crate::settings::get().prefetch_frames;
+    let signature = template_signature(template);
+    let mut state = STATES.entry(effect_id).or_insert_with(|| PrefetchState {
+        signature,
+        last_frame: served_frame,
+        end: served_frame,
+        task: None,
+    });
+
+    if state.signature != signature {
+        log::debug!(
+            "Parameters changed for effect_id {}, stopping prefetch",
+            effect_id
+        );
+        state.stop();
+        state.signature = signature;
+        state.last_frame = served_frame;
+        state.end = served_frame;
+        return;
+    }
+    let is_sequential = served_frame >= state.last_frame && served_frame <= state.end + 1;
+    state.last_frame = served_frame;
+    if !is_sequential {
+        log::debug!(
+            "Seek detected for effect_id {} (frame {}), restarting prefetch",
+            effect_id,
+            served_frame
+        );
+        state.stop();
+        state.end = served_frame;
+    }
+    if lookahead == 0 {
+        return;
+    }
+    let is_running = state.task.as_ref().is_some_and(|task| !task.is_finished());
+    // 実行中なら、先読みした分の半分まで追いつかれるまで待つ
+    if is_running && state.end >= served_frame + lookahead / 2 {
+        return;
+    }
+
+    let start = state.end.max(served_frame) + 1;
+    let end = (served_frame + lookahead).min(template.frame_info.total_frames.saturating_sub(1));
+    if start > end {
+        return;
+    }
+    let requests = (start..=end)
+        .map(|frame| request_at_frame(template, frame))
+        .collect::<Vec<_>>();
+
+    let spawned = Vi5Aux2::with_instance(|instance| -> anyhow::Result<_> {
+        let runtime = instance
+            .runtime
+            .read()
+            .map_err(|e| anyhow::anyhow!("Failed to acquire runtime read lock: {}", e))?;
+        let runtime = runtime
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("tokio runtime is not initialized"))?;
+        let server = instance.server.clone();
+        Ok(runtime
+            .spawn(prefetch_task(
+                cache,
+                effect_id,
+                server,
+                requests,
+                batch_size.max(1),
+            ))
+            .abort_handle())
+    });
+    match spawned {
+        Ok(task) => {
+            log::debug!(
+                "Prefetching frames {}..={} for effect_id {}",
+                start,
+                end,
+                effect_id
+            );
+            state.stop();
+            state.task = Some(task);
+            state.end = end;
+        }
+        Err(e) => {
+            log::warn!(
+                "Failed to start prefetch for effect_id {}: {}",
+                effect_id,
+                e
+            );
+        }
+    }
+}
+
+async fn prefetch_task(
+    cache: &'static RenderCache,
+    effect_id: i32,
+    server: crate::Vi5Server,
+    requests: Vec<vi5_cef::RenderRequest>,
+    batch_size: usize,
+) {
+    let mut seen_keys = std::collections::HashSet::new();
+    for chunk in requests.chunks(batch_size) {
+        let chunk = chunk
+            .iter()
+            .map(|request| {
+                (
+                    crate::module::compute_cache_key(request),
+                    None,
+                    request.clone(),
+                )
+            })
+            .filter(|(key, _, _)| seen_keys.insert(*key) && !cache.contains(effect_id, *key))
+            .collect::<Vec<_>>();
+        if chunk.is_empty() {
+            continue;
+        }
+        // チャンクごとにロックを取り直して、表示中のフレームの描画を長く待たせないようにする
+        if let Err(e) =
+            crate::module::render_and_store(server.clone(), effect_id, chunk, None).await
+        {
+            log::warn!("Prefetch failed for effect_id {}: {}", effect_id, e);
+            return;
+        }
+    }
+}
+
+/// すべての先読みを止める。
+pub fn stop_all() {
+    for mut state in STATES.iter_mut() {
+        state.stop();
+    }
+    STATES.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(is_offline: bool) -> vi5_cef::RenderRequest {
+        vi5_cef::RenderRequest {
+            object: "/src/test.object.ts".to_string(),
+            object_id: 1,
+            frame_info: vi5_cef::FrameInfo {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                screen_width: 1920,
+                screen_height: 1080,
+                current_frame: 0,
+                current_time: 0.0,
+                total_frames: 60,
+                total_time: 2.0,
+                framerate: 30.0,
+                global_frame: 0,
+                global_time: 0.0,
+            },
+            parameters: vec![],
+            is_offline,
+            seed: 0,
+            input_image: None,
+        }
+    }
+
+    fn object_info(stateful: bool) -> vi5_cef::ObjectInfo {
+        vi5_cef::ObjectInfo {
+            id: "/src/test.object.ts".to_string(),
+            label: "Test".to_string(),
+            parameter_definitions: vec![],
+            stateful,
+            version: String::new(),
+            dependencies: None,
+            kind: vi5_cef::ObjectKind::Generator,
+        }
+    }
+
+    #[test]
+    fn prefetches_stateless_preview_renders() {
+        assert!(should_prefetch(&request(false), Some(&object_info(false))));
+        assert!(should_prefetch(&request(false), None));
+    }
+
+    #[test]
+    fn skips_stateful_objects() {
+        assert!(!should_prefetch(&request(false), Some(&object_info(true))));
+    }
+
+    #[test]
+    fn skips_offline_renders() {
+        assert!(!should_prefetch(&request(true), Some(&object_info(false))));
+    }
+
+    #[test]
+    fn skips_filters() {
+        let mut request = request(false);
+        request.input_image = Some(vi5_cef::InputImage {
+            width: 1,
+            height: 1,
+            image_data: vec![0; 4],
+        });
+        assert!(!should_prefetch(&request, Some(&object_info(false))));
+    }
+}
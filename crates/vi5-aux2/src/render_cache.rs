@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use aviutl2::log;
+
+const STATS_LOG_INTERVAL: u64 = 500;
+
+#[derive(Debug)]
+pub struct RenderCacheEntry {
+    pub image_data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+type CacheKey = (i32, u64);
+
+/// `(effect_id, cache_key)`をキーにした、バイト数で上限を決めるLRUキャッシュ。
+pub struct RenderCache {
+    inner: std::sync::Mutex<Inner>,
+}
+
+struct Inner {
+    budget: usize,
+    used: usize,
+    clock: u64,
+    entries: HashMap<CacheKey, (Arc<RenderCacheEntry>, u64)>,
+    recency: BTreeMap<u64, CacheKey>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, key: CacheKey) {
+        self.clock += 1;
+        if let Some((_, last_used)) = self.entries.get_mut(&key) {
+            self.recency.remove(last_used);
+            *last_used = self.clock;
+            self.recency.insert(self.clock, key);
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((entry, last_used)) = self.entries.remove(key) {
+            self.recency.remove(&last_used);
+            self.used -= entry.image_data.len();
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.used > self.budget {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            self.remove(&key);
+            self.evictions += 1;
+        }
+    }
+
+    fn log_stats(&self) {
+        let lookups = self.hits + self.misses;
+        log::info!(
+            "Render cache: {} hits / {} misses ({:.1}%), {} entries, {:.1} / {:.1} MiB, {} evictions",
+            self.hits,
+            self.misses,
+            self.hits as f64 / lookups.max(1) as f64 * 100.0,
+            self.entries.len(),
+            self.used as f64 / (1024.0 * 1024.0),
+            self.budget as f64 / (1024.0 * 1024.0),
+            self.evictions
+        );
+    }
+}
+
+impl RenderCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            inner: std::sync::Mutex::new(Inner {
+                budget,
+                used: 0,
+                clock: 0,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    /// ヒット・ミスを記録してエントリを取得する。
+    pub fn get(&self, effect_id: i32, cache_key: u64) -> Option<Arc<RenderCacheEntry>> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = (effect_id, cache_key);
+        let entry = inner.entries.get(&key).map(|(entry, _)| entry.clone());
+        if entry.is_some() {
+            inner.hits += 1;
+            inner.touch(key);
+        } else {
+            inner.misses += 1;
+        }
+        if (inner.hits + inner.misses) % STATS_LOG_INTERVAL == 0 {
+            inner.log_stats();
+        }
+        entry
+    }
+
+    pub fn contains(&self, effect_id: i32, cache_key: u64) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .entries
+            .contains_key(&(effect_id, cache_key))
+    }
+
+    pub fn insert(
+        &self,
+        effect_id: i32,
+        cache_key: u64,
+        entry: RenderCacheEntry,
+    ) -> Arc<RenderCacheEntry> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = (effect_id, cache_key);
+        let entry = Arc::new(entry);
+        inner.remove(&key);
+        inner.used += entry.image_data.len();
+        inner.clock += 1;
+        let clock = inner.clock;
+        inner.entries.insert(key, (entry.clone(), clock));
+        inner.recency.insert(clock, key);
+        inner.evict();
+        entry
+    }
+
+    pub fn set_budget(&self, budget: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.budget = budget;
+        inner.evict();
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.log_stats();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.used = 0;
+    }
+}
@@ -1,119 +1,24 @@
---PARAMETER_DEFINITIONS--
---LABEL--
---group:Performance
---track@batch_size:Batch Size,0,50,0,1
---check@freeze:Freeze,false
+--HEADER--
 --END_HEADER
-local internal = obj.module("--MODULE_NAME--")
-
-local function to_json(v)
-  if type(v) == "number" then
-    return tostring(v)
-  elseif type(v) == "string" then
-    return string.format("%q", v)
-  elseif type(v) == "boolean" then
-    return tostring(v)
-  elseif type(v) == "table" then
-    local is_array = (#v > 0)
-    local items = {}
-    if is_array then
-      for i = 1, #v do
-        items[#items + 1] = to_json(v[i])
-      end
-      return "[" .. table.concat(items, ",") .. "]"
-    else
-      for k, val in pairs(v) do
-        items[#items + 1] = string.format("%q:%s", k, to_json(val))
-      end
-      return "{" .. table.concat(items, ",") .. "}"
-    end
-  else
-    return "null"
-  end
-end
-
-local object_id = "--OBJECT_ID--"
-local params_keys = {
-  --PARAMETER_KEYS--
-}
-local param_values = {
-  --PARAMETER_VALUES--
-}
-local param_types = {
-  --PARAMETER_TYPES--
-}
-local function serialize_param_at(frame_offset)
-  local serialized_params = {};
-  for i = 1, #params_keys do
-    local key = params_keys[i]
-    local value = param_values[i]
-    local kind = param_types[i]
-    if kind == "Color" then
-      if value == nil then
-        serialized_params[key] = { type = kind, value = 0 }
-      else
-        serialized_params[key] = { type = kind, value = 0xff000000 + value }
-      end
-    elseif type(v) == "number" then
-      serialized_params[key] = {
-        type = kind,
-        value = obj.getvalue(string:format("track.VI5_AUX2_%s", key), obj.time + frame_offset * (1 / obj.framerate)) or value
-      }
-    else
-      serialized_params[key] = { type = kind, value = value }
-    end
-  end
-  return serialized_params
-end
-
-local function get_frame_info_at(frame_offset)
-  local time = obj.time + frame_offset * (1 / obj.framerate)
-  local starting_frame = obj.getvalue("frame_s")
-  local global_frame = starting_frame + obj.frame + frame_offset
-  local frame_info = {
-    x = obj.getvalue("x", time),
-    y = obj.getvalue("y", time),
-    z = obj.getvalue("z", time),
-    canvas_width = obj.screen_w,
-    canvas_height = obj.screen_h,
-    current_frame = obj.frame + frame_offset,
-    current_time = time,
-    total_frames = obj.totalframe,
-    total_time = obj.totaltime,
-    framerate = obj.framerate,
-    global_frame = global_frame,
-    global_time = global_frame / obj.framerate
-  }
-  return frame_info
-end
-
-local batch_serialized_params = {}
-local batch_frame_info = {}
+--COMMON--
 
 if batch_size <= 0 then
-  batch_size = internal.get_batch_size(obj.effect_id)
+  batch_size = internal.get_batch_size(obj.effect_id, obj.getinfo("saving"))
 end
 
+local frame_count = 0
 for i = 0, batch_size - 1 do
   if obj.frame + i >= obj.totalframe then
     break
   end
-  batch_serialized_params[i + 1] = serialize_param_at(i)
-  batch_frame_info[i + 1] = get_frame_info_at(i)
+  frame_count = frame_count + 1
+end
+encode_value(payload, frame_count)
+for i = 0, frame_count - 1 do
+  encode_frame(payload, i)
 end
 
-local render_params = {
-  object_name = object_id,
-  effect_id = obj.effect_id,
-  freeze = freeze,
-  offline = obj.getinfo("saving"),
-}
-
-local image, w, h = internal.call_object(
-  to_json(render_params),
-  to_json(batch_serialized_params),
-  to_json(batch_frame_info)
-)
+local image, w, h = internal.call_object(table.concat(payload))
 obj.putpixeldata("object", image, w, h, "rgba")
 internal.free_image(obj.effect_id)
 
@@ -0,0 +1,121 @@
+-- script.luaとscript_filter.luaの共通部分。script_generator.rsが両方のテンプレートに埋め込む
+local internal = obj.module("--MODULE_NAME--")
+
+-- Rust側（lua_call.rs）と対応する、型タグ付きのエンコード
+local function encode_value(buffer, v)
+  local t = type(v)
+  if t == "number" then
+    buffer[#buffer + 1] = "n" .. string.format("%.17g", v) .. ";"
+  elseif t == "string" then
+    buffer[#buffer + 1] = "s" .. #v .. ":" .. v
+  elseif t == "boolean" then
+    buffer[#buffer + 1] = v and "b1" or "b0"
+  elseif v == nil then
+    buffer[#buffer + 1] = "_"
+  elseif t == "table" then
+    -- Vector2などは成分を順に並べる
+    for _, item in ipairs(v) do
+      encode_value(buffer, item)
+    end
+  else
+    error("vi5.aux2: cannot encode a value of type " .. t)
+  end
+end
+
+local object_id = "--OBJECT_ID--"
+local params_keys = {
+  --PARAMETER_KEYS--
+}
+local param_values = {
+  --PARAMETER_VALUES--
+}
+local param_types = {
+  --PARAMETER_TYPES--
+}
+-- 成分ごとにトラックバーが分かれている型。並びはscript_generator.rsの`components`と同じ
+local param_components = {
+  Vector2 = { "x", "y" },
+  Vector3 = { "x", "y", "z" },
+  Range = { "start", "end" },
+}
+-- バッチ内のフレームは、トラックバーの値をそのフレームの時刻で取り直す
+local function serialize_param_at(frame_offset)
+  local time = obj.time + frame_offset / obj.framerate
+  local serialized_params = {};
+  for i = 1, #params_keys do
+    local key = params_keys[i]
+    local value = param_values[i]
+    local kind = param_types[i]
+    if kind == "Color" then
+      -- 値は{色, アルファ}。色が未選択なら透明にする
+      if value[1] == nil then
+        serialized_params[i] = 0
+      else
+        local alpha = obj.getvalue(string.format("track.VI5_AUX2_%s_alpha", key), time) or value[2]
+        alpha = math.max(0, math.min(255, math.floor(alpha + 0.5)))
+        serialized_params[i] = alpha * 0x1000000 + value[1]
+      end
+    elseif kind == "File" then
+      serialized_params[i] = value or ""
+    elseif kind == "Number" then
+      serialized_params[i] = obj.getvalue(string.format("track.VI5_AUX2_%s", key), time) or value
+    elseif param_components[kind] then
+      local components = {}
+      for j, component in ipairs(param_components[kind]) do
+        components[j] = obj.getvalue(string.format("track.VI5_AUX2_%s_%s", key, component), time)
+          or value[j]
+      end
+      serialized_params[i] = components
+    else
+      serialized_params[i] = value
+    end
+  end
+  return serialized_params
+end
+
+local function get_frame_info_at(frame_offset)
+  local time = obj.time + frame_offset / obj.framerate
+  local starting_frame = obj.getvalue("frame_s")
+  local global_frame = starting_frame + obj.frame + frame_offset
+  -- 並びはlua_call.rsのLuaFrameInfoと同じ
+  return {
+    obj.getvalue("x", time),
+    obj.getvalue("y", time),
+    obj.getvalue("z", time),
+    obj.screen_w,
+    obj.screen_h,
+    obj.frame + frame_offset,
+    time,
+    obj.totalframe,
+    obj.totaltime,
+    obj.framerate,
+    global_frame,
+    global_frame / obj.framerate,
+  }
+end
+
+local payload = {}
+encode_value(payload, object_id)
+encode_value(payload, obj.effect_id)
+encode_value(payload, freeze)
+encode_value(payload, obj.getinfo("saving"))
+encode_value(payload, math.floor(tonumber(seed) or 0) % 4294967296)
+encode_value(payload, error_image)
+
+encode_value(payload, #params_keys)
+for i = 1, #params_keys do
+  encode_value(payload, params_keys[i])
+  encode_value(payload, param_types[i])
+end
+
+-- 1フレーム分のframe_infoとパラメーターの値を書き込む
+local function encode_frame(buffer, frame_offset)
+  local frame_info = get_frame_info_at(frame_offset)
+  for j = 1, #frame_info do
+    encode_value(buffer, frame_info[j])
+  end
+  local serialized_params = serialize_param_at(frame_offset)
+  for j = 1, #params_keys do
+    encode_value(buffer, serialized_params[j])
+  end
+end
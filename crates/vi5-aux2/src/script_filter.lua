@@ -0,0 +1,15 @@
+--HEADER--
+--END_HEADER
+--COMMON--
+
+-- 入力画像は現在のフレームのものしか取れないので、1フレームずつ描画する
+encode_value(payload, 1)
+encode_frame(payload, 0)
+
+local input, input_w, input_h = obj.getpixeldata("object", "rgba")
+internal.set_input_image(obj.effect_id, input, input_w, input_h)
+local image, w, h = internal.call_object(table.concat(payload))
+obj.putpixeldata("object", image, w, h, "rgba")
+internal.free_image(obj.effect_id)
+
+-- vim:set ft=lua:
@@ -0,0 +1,997 @@
+use std::fmt::Write;
+
+const OBJECT_TEMPLATE: &str = include_str!("./script.lua");
+const FILTER_TEMPLATE: &str = include_str!("./script_filter.lua");
+// 両方のテンプレートの`--COMMON--`に入れる、エンコードなどの共通部分
+const COMMON_TEMPLATE: &str = include_str!("./script_common.lua");
+// スクリプトのパラメーターはグローバル変数になるので、組み込みのものと被らないようにする
+pub const VAR_PREFIX: &str = "VI5_AUX2_";
+const MAX_FILE_NAME_CHARS: usize = 100;
+
+/// AviUtl2のスクリプトのヘッダーにある、1行分のディレクティブ。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    // AviUtl2には無視されるコメント行
+    Comment(String),
+    Label(String),
+    Group(String),
+    Track {
+        name: String,
+        label: String,
+        min: f64,
+        max: f64,
+        default: f64,
+        step: &'static str,
+    },
+    Value {
+        name: String,
+        label: String,
+        // Luaの式
+        default: String,
+    },
+    Text {
+        name: String,
+        label: String,
+        default: String,
+    },
+    Check {
+        name: String,
+        label: String,
+        default: bool,
+    },
+    Color {
+        name: String,
+        label: String,
+        default: Option<u32>,
+    },
+    File {
+        name: String,
+        label: String,
+        filters: Vec<String>,
+    },
+    // 値は選択肢の番号
+    Select {
+        name: String,
+        label: String,
+        default: usize,
+        options: Vec<String>,
+    },
+}
+
+impl Directive {
+    pub fn render(&self) -> String {
+        match self {
+            // `--`の直後に空白を入れて、ディレクティブとして読まれないようにする
+            Directive::Comment(comment) => format!("-- {}", escape_comment(comment)),
+            Directive::Label(label) => format!("--label:{}", label),
+            Directive::Group(label) => format!("--group:{}", escape_label(label)),
+            Directive::Track {
+                name,
+                label,
+                min,
+                max,
+                default,
+                step,
+            } => format!(
+                "--track@{}:{},{},{},{},{}",
+                name,
+                escape_label(label),
+                min,
+                max,
+                default,
+                step
+            ),
+            Directive::Value {
+                name,
+                label,
+                default,
+            } => format!("--value@{}:{},{}", name, escape_label(label), default),
+            Directive::Text {
+                name,
+                label,
+                default,
+            } => format!(
+                "--text@{}:{},{}",
+                name,
+                escape_label(label),
+                lua_string(default)
+            ),
+            Directive::Check {
+                name,
+                label,
+                default,
+            } => format!("--check@{}:{},{}", name, escape_label(label), default),
+            Directive::Color {
+                name,
+                label,
+                default,
+            } => format!(
+                "--color@{}:{},{}",
+                name,
+                escape_label(label),
+                match default {
+                    Some(color) => format!("0x{:06X}", color),
+                    None => "nil".to_string(),
+                }
+            ),
+            Directive::File {
+                name,
+                label,
+                filters,
+            } => {
+                let mut line = format!("--file@{}:{}", name, escape_label(label));
+                // パターンは`;`で区切る
+                let filters = filters
+                    .iter()
+                    .map(|filter| {
+                        filter
+                            .chars()
+                            .filter(|c| !matches!(c, ',' | ';' | '|') && !c.is_control())
+                            .collect::<String>()
+                    })
+                    .filter(|filter| !filter.is_empty())
+                    .collect::<Vec<_>>();
+                if !filters.is_empty() {
+                    write!(line, ",{}", filters.join(";")).unwrap();
+                }
+                line
+            }
+            Directive::Select {
+                name,
+                label,
+                default,
+                options,
+            } => {
+                let mut line = format!("--select@{}:{}={}", name, escape_option(label), default);
+                for (index, option) in options.iter().enumerate() {
+                    write!(line, ",{}={}", escape_option(option), index).unwrap();
+                }
+                line
+            }
+        }
+    }
+}
+
+/// ディレクティブの区切り文字（`,`・`:`）と改行を、表示上は同じに見える文字に置き換える。
+pub fn escape_label(label: &str) -> String {
+    label
+        .chars()
+        .filter_map(|c| match c {
+            ',' => Some('，'),
+            ':' => Some('：'),
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// コメントは1行に収める。
+fn escape_comment(comment: &str) -> String {
+    comment
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// `--select@`では`=`も区切り文字になる。
+fn escape_option(label: &str) -> String {
+    escape_label(label).replace('=', "＝")
+}
+
+/// Luaの文字列リテラルにする。
+pub fn lua_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => literal.push_str(r"\\"),
+            '"' => literal.push_str(r#"\""#),
+            '\n' => literal.push_str(r"\n"),
+            '\r' => literal.push_str(r"\r"),
+            '\t' => literal.push_str(r"\t"),
+            c if c.is_control() => {
+                let mut buffer = [0; 4];
+                for byte in c.encode_utf8(&mut buffer).bytes() {
+                    write!(literal, r"\{:03}", byte).unwrap();
+                }
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// パラメーターのキーはLuaの変数名になるので、識別子として使える文字だけを許す。
+pub fn validate_key(key: &str) -> anyhow::Result<()> {
+    let mut chars = key.chars();
+    let is_valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid {
+        anyhow::bail!(
+            "Invalid parameter key {:?}: keys must start with a letter or underscore and contain only ASCII letters, digits and underscores",
+            key
+        );
+    }
+    Ok(())
+}
+
+/// Windowsでファイル名・フォルダ名として使えるようにする。
+pub fn sanitize_file_name(name: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+    let sanitized = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_FILE_NAME_CHARS)
+        .collect::<String>();
+    let sanitized = sanitized.trim_end_matches(['.', ' ']).trim_start();
+    if sanitized.is_empty() {
+        return "_".to_string();
+    }
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return format!("_{}", sanitized);
+    }
+    sanitized.to_string()
+}
+
+/// 成分ごとにトラックバーを分けるパラメーターの、成分の名前とラベル。
+/// 変数名は`VI5_AUX2_<キー>_<成分>`になる。並びはscript_common.luaの`param_components`と同じ。
+fn components(parameter_type: &vi5_cef::ParameterType) -> &'static [(&'static str, &'static str)] {
+    match parameter_type {
+        vi5_cef::ParameterType::Vector2 { .. } => &[("x", "X"), ("y", "Y")],
+        vi5_cef::ParameterType::Vector3 { .. } => &[("x", "X"), ("y", "Y"), ("z", "Z")],
+        vi5_cef::ParameterType::Range { .. } => &[("start", "Start"), ("end", "End")],
+        _ => &[],
+    }
+}
+
+/// パラメーターが使うスクリプトの変数名。
+fn variable_names(param: &vi5_cef::ParameterDefinition) -> Vec<String> {
+    let name = format!("{VAR_PREFIX}{}", param.key);
+    match &param.parameter_type {
+        // 色の選択欄ではアルファを選べないので、別にトラックバーを置く
+        vi5_cef::ParameterType::Color => {
+            let alpha = format!("{name}_alpha");
+            vec![name, alpha]
+        }
+        parameter_type => match components(parameter_type) {
+            [] => vec![name],
+            components => components
+                .iter()
+                .map(|(component, _)| format!("{name}_{component}"))
+                .collect(),
+        },
+    }
+}
+
+fn track(
+    key: &str,
+    name: String,
+    label: String,
+    step: vi5_cef::NumberStep,
+    min: f64,
+    max: f64,
+    default: f64,
+) -> anyhow::Result<Directive> {
+    if !(min.is_finite() && max.is_finite() && default.is_finite()) {
+        anyhow::bail!(
+            "Parameter {:?} has a non-finite range or default ({}, {}, {})",
+            key,
+            min,
+            max,
+            default
+        );
+    }
+    Ok(Directive::Track {
+        name,
+        label,
+        min,
+        max,
+        default,
+        step: step.as_str(),
+    })
+}
+
+/// 成分ごとのトラックバーを作る。
+fn component_tracks(
+    param: &vi5_cef::ParameterDefinition,
+    number_components: &[vi5_cef::NumberComponent],
+    defaults: &[f64],
+) -> anyhow::Result<Vec<Directive>> {
+    components(&param.parameter_type)
+        .iter()
+        .zip(variable_names(param))
+        .zip(number_components.iter().zip(defaults))
+        .map(|(((_, component_label), name), (component, &default))| {
+            track(
+                &param.key,
+                name,
+                format!("{} {}", param.label, component_label),
+                component.step,
+                component.min,
+                component.max,
+                default,
+            )
+        })
+        .collect()
+}
+
+/// パラメーターのディレクティブを作る。説明があれば、その前にコメントとして書く。
+fn parameter_directives(param: &vi5_cef::ParameterDefinition) -> anyhow::Result<Vec<Directive>> {
+    let mut directives = vec![];
+    if let Some(description) = param
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|description| !description.is_empty())
+    {
+        directives.push(Directive::Comment(description.to_string()));
+    }
+    directives.extend(value_directives(param)?);
+    Ok(directives)
+}
+
+fn value_directives(param: &vi5_cef::ParameterDefinition) -> anyhow::Result<Vec<Directive>> {
+    validate_key(&param.key)?;
+    let default_value = param.default_value.as_ref().map(|default| &default.value);
+    let name = format!("{VAR_PREFIX}{}", param.key);
+    let label = param.label.clone();
+    Ok(vec![match &param.parameter_type {
+        vi5_cef::ParameterType::String => Directive::Value {
+            name,
+            label,
+            default: lua_string(match default_value {
+                Some(vi5_cef::ParameterValue::Str(value)) => value,
+                _ => "",
+            }),
+        },
+        vi5_cef::ParameterType::Text => Directive::Text {
+            name,
+            label,
+            default: match default_value {
+                Some(vi5_cef::ParameterValue::Text(value)) => value.clone(),
+                _ => String::new(),
+            },
+        },
+        vi5_cef::ParameterType::Boolean => Directive::Check {
+            name,
+            label,
+            default: matches!(default_value, Some(vi5_cef::ParameterValue::Bool(true))),
+        },
+        &vi5_cef::ParameterType::Number { step, min, max } => {
+            let default = match default_value {
+                Some(vi5_cef::ParameterValue::Number(value)) => *value,
+                _ => min,
+            };
+            track(&param.key, name, label, step, min, max, default)?
+        }
+        vi5_cef::ParameterType::Color => {
+            let (default, alpha) = match default_value {
+                Some(vi5_cef::ParameterValue::Color(color)) => (
+                    Some(((color.r as u32) << 16) | ((color.g as u32) << 8) | color.b as u32),
+                    color.a,
+                ),
+                _ => (None, 255),
+            };
+            return Ok(vec![
+                Directive::Color {
+                    name: name.clone(),
+                    label: label.clone(),
+                    default,
+                },
+                Directive::Track {
+                    name: format!("{name}_alpha"),
+                    label: format!("{label} Alpha"),
+                    min: 0.0,
+                    max: 255.0,
+                    default: alpha as f64,
+                    step: "1",
+                },
+            ]);
+        }
+        vi5_cef::ParameterType::File { filters } => Directive::File {
+            name,
+            label,
+            filters: filters.clone(),
+        },
+        vi5_cef::ParameterType::Select { options } => {
+            if options.is_empty() {
+                anyhow::bail!("Select parameter {:?} has no options", param.key);
+            }
+            Directive::Select {
+                name,
+                label,
+                default: match default_value {
+                    Some(vi5_cef::ParameterValue::Select(value)) => options
+                        .iter()
+                        .position(|(_, option)| option == value)
+                        .unwrap_or(0),
+                    _ => 0,
+                },
+                options: options.iter().map(|(label, _)| label.clone()).collect(),
+            }
+        }
+        vi5_cef::ParameterType::Vector2 { x, y } => {
+            let defaults = match default_value {
+                Some(&vi5_cef::ParameterValue::Vector2 { x, y }) => [x, y],
+                _ => [x.min, y.min],
+            };
+            return component_tracks(param, &[*x, *y], &defaults);
+        }
+        vi5_cef::ParameterType::Vector3 { x, y, z } => {
+            let defaults = match default_value {
+                Some(&vi5_cef::ParameterValue::Vector3 { x, y, z }) => [x, y, z],
+                _ => [x.min, y.min, z.min],
+            };
+            return component_tracks(param, &[*x, *y, *z], &defaults);
+        }
+        vi5_cef::ParameterType::Range { start, end } => {
+            let defaults = match default_value {
+                Some(&vi5_cef::ParameterValue::Range { start, end }) => [start, end],
+                _ => [start.min, end.min],
+            };
+            return component_tracks(param, &[*start, *end], &defaults);
+        }
+    }])
+}
+
+fn lua_type_name(parameter_type: &vi5_cef::ParameterType) -> &'static str {
+    match parameter_type {
+        vi5_cef::ParameterType::String => "Str",
+        vi5_cef::ParameterType::Text => "Text",
+        vi5_cef::ParameterType::Boolean => "Bool",
+        vi5_cef::ParameterType::Number { .. } => "Number",
+        vi5_cef::ParameterType::Color => "Color",
+        vi5_cef::ParameterType::Select { .. } => "Select",
+        vi5_cef::ParameterType::File { .. } => "File",
+        vi5_cef::ParameterType::Vector2 { .. } => "Vector2",
+        vi5_cef::ParameterType::Vector3 { .. } => "Vector3",
+        vi5_cef::ParameterType::Range { .. } => "Range",
+    }
+}
+
+/// パラメーターを`order`順に並べ、グループごとにまとめる。
+/// グループのないパラメーターを先頭に、グループは最初に出てきた順に並べる。
+fn group_parameters(
+    parameters: &[vi5_cef::ParameterDefinition],
+) -> Vec<(Option<&str>, Vec<&vi5_cef::ParameterDefinition>)> {
+    let mut sorted = parameters.iter().collect::<Vec<_>>();
+    // 安定ソートなので、`order`が同じなら宣言順になる
+    sorted.sort_by_key(|param| param.order.unwrap_or(i32::MAX));
+    let mut groups: Vec<(Option<&str>, Vec<&vi5_cef::ParameterDefinition>)> = vec![(None, vec![])];
+    for param in sorted {
+        let group = param.group.as_deref();
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, params)) => params.push(param),
+            None => groups.push((group, vec![param])),
+        }
+    }
+    groups
+}
+
+// builtin_directivesが使うグループ名。オブジェクトのグループと被ると見分けがつかなくなる
+const BUILTIN_GROUPS: &[&str] = &["Export", "Performance", "Debug"];
+
+/// オブジェクトの定義に依らない、vi5.aux2自体の設定項目。
+fn builtin_directives(kind: vi5_cef::ObjectKind) -> Vec<Directive> {
+    let mut directives = vec![
+        Directive::Group("Export".to_string()),
+        Directive::Value {
+            name: "seed".to_string(),
+            label: "Random Seed".to_string(),
+            default: "0".to_string(),
+        },
+        Directive::Group("Performance".to_string()),
+    ];
+    // フィルターは1フレームずつしか描画できない
+    if kind == vi5_cef::ObjectKind::Generator {
+        directives.push(Directive::Track {
+            name: "batch_size".to_string(),
+            label: "Batch Size".to_string(),
+            min: 0.0,
+            max: 50.0,
+            default: 0.0,
+            step: "1",
+        });
+    }
+    directives.extend([
+        Directive::Check {
+            name: "freeze".to_string(),
+            label: "Freeze".to_string(),
+            default: false,
+        },
+        Directive::Group("Debug".to_string()),
+        Directive::Check {
+            name: "error_image".to_string(),
+            label: "Show Error Image".to_string(),
+            default: true,
+        },
+    ]);
+    directives
+}
+
+// 生成するスクリプトの拡張子
+pub const SCRIPT_EXTENSIONS: &[&str] = &["obj2", "anm2"];
+
+/// オブジェクトのスクリプトのファイル名。
+pub fn file_name(object: &vi5_cef::ObjectInfo) -> String {
+    let extension = match object.kind {
+        vi5_cef::ObjectKind::Generator => "obj2",
+        vi5_cef::ObjectKind::Filter => "anm2",
+    };
+    format!("{}.{}", sanitize_file_name(&object.label), extension)
+}
+
+#[derive(Debug)]
+pub struct GeneratedScript {
+    pub file_name: String,
+    pub content: String,
+}
+
+/// オブジェクトのスクリプトを作る。フィルターは.anm2、それ以外は.obj2になる。
+pub fn generate(
+    project_name: &str,
+    module_name: &str,
+    object: &vi5_cef::ObjectInfo,
+) -> anyhow::Result<GeneratedScript> {
+    let mut directives = vec![];
+    for (group, params) in group_parameters(&object.parameter_definitions) {
+        if let Some(group) = group {
+            if BUILTIN_GROUPS
+                .iter()
+                .any(|builtin| builtin.eq_ignore_ascii_case(group.trim()))
+            {
+                anyhow::bail!(
+                    "Parameter group {:?} conflicts with a built-in vi5.aux2 group",
+                    group
+                );
+            }
+            directives.push(Directive::Group(group.to_string()));
+        }
+        for param in params {
+            directives.extend(parameter_directives(param)?);
+        }
+    }
+    // Vector2などの成分の変数名が、他のパラメーターと被っていないか確認する
+    let mut variables = std::collections::HashSet::new();
+    for param in &object.parameter_definitions {
+        for variable in variable_names(param) {
+            if !variables.insert(variable.clone()) {
+                anyhow::bail!(
+                    "Parameter {:?} conflicts with another parameter's script variable {}",
+                    param.key,
+                    variable
+                );
+            }
+        }
+    }
+    // `\`はラベルの階層の区切りになる
+    directives.push(Directive::Label(format!(
+        "vi5.aux2\\{}",
+        escape_label(project_name).replace('\\', "＼")
+    )));
+    directives.extend(builtin_directives(object.kind));
+    let header = directives
+        .iter()
+        .map(Directive::render)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let keys = object
+        .parameter_definitions
+        .iter()
+        .map(|param| lua_string(&param.key))
+        .collect::<Vec<_>>()
+        .join(",");
+    let values = object
+        .parameter_definitions
+        .iter()
+        .map(|param| match variable_names(param).as_slice() {
+            [name] => name.clone(),
+            // 成分ごとの値をテーブルにまとめる
+            names => format!("{{{}}}", names.join(",")),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let types = object
+        .parameter_definitions
+        .iter()
+        .map(|param| lua_string(lua_type_name(&param.parameter_type)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let template = match object.kind {
+        vi5_cef::ObjectKind::Generator => OBJECT_TEMPLATE,
+        vi5_cef::ObjectKind::Filter => FILTER_TEMPLATE,
+    };
+    let content = template
+        .replace("--COMMON--", COMMON_TEMPLATE.trim_end())
+        .replace("--HEADER--", &header)
+        .replace(r#""--MODULE_NAME--""#, &lua_string(module_name))
+        .replace("--PARAMETER_KEYS--", &keys)
+        .replace("--PARAMETER_VALUES--", &values)
+        .replace("--PARAMETER_TYPES--", &types)
+        .replace(r#""--OBJECT_ID--""#, &lua_string(&object.id));
+    Ok(GeneratedScript {
+        file_name: file_name(object),
+        content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_with(
+        parameter_type: vi5_cef::ParameterType,
+        default: vi5_cef::ParameterValue,
+    ) -> vi5_cef::ObjectInfo {
+        vi5_cef::ObjectInfo {
+            id: "/src/test.object.ts".to_string(),
+            label: "Test Object".to_string(),
+            parameter_definitions: vec![vi5_cef::ParameterDefinition {
+                key: "value".to_string(),
+                parameter_type,
+                label: "Value".to_string(),
+                default_value: Some(vi5_cef::Parameter {
+                    key: "value".to_string(),
+                    value: default,
+                }),
+                group: None,
+                description: None,
+                order: None,
+            }],
+            stateful: false,
+            version: String::new(),
+            dependencies: None,
+            kind: vi5_cef::ObjectKind::Generator,
+        }
+    }
+
+    fn header(content: &str) -> &str {
+        let end = content.find("--END_HEADER").expect("no header") + "--END_HEADER".len();
+        &content[..end]
+    }
+
+    // UPDATE_GOLDENS=1 で実行すると期待値を書き換える
+    fn assert_golden(name: &str, object: &vi5_cef::ObjectInfo) {
+        let generated = generate("test", "vi5_aux2", object).unwrap();
+        let actual = format!("{}\n", header(&generated.content));
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join("script_header")
+            .join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_GOLDENS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e))
+            .replace("\r\n", "\n");
+        assert_eq!(actual, expected, "golden mismatch for {}", name);
+    }
+
+    #[test]
+    fn golden_string() {
+        assert_golden(
+            "string",
+            &object_with(
+                vi5_cef::ParameterType::String,
+                vi5_cef::ParameterValue::Str("hello \"world\"\\".to_string()),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_text() {
+        assert_golden(
+            "text",
+            &object_with(
+                vi5_cef::ParameterType::Text,
+                vi5_cef::ParameterValue::Text("line1\nline2".to_string()),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_boolean() {
+        assert_golden(
+            "boolean",
+            &object_with(
+                vi5_cef::ParameterType::Boolean,
+                vi5_cef::ParameterValue::Bool(true),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_number() {
+        assert_golden(
+            "number",
+            &object_with(
+                vi5_cef::ParameterType::Number {
+                    step: vi5_cef::NumberStep::PointOne,
+                    min: -10.0,
+                    max: 100.0,
+                },
+                vi5_cef::ParameterValue::Number(12.5),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_color() {
+        assert_golden(
+            "color",
+            &object_with(
+                vi5_cef::ParameterType::Color,
+                vi5_cef::ParameterValue::Color(vi5_cef::Color {
+                    r: 255,
+                    g: 128,
+                    b: 0,
+                    a: 255,
+                }),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_select() {
+        assert_golden(
+            "select",
+            &object_with(
+                vi5_cef::ParameterType::Select {
+                    options: vec![
+                        ("Circle".to_string(), "circle".to_string()),
+                        ("Square, Rounded".to_string(), "rounded".to_string()),
+                        ("a=b".to_string(), "equals".to_string()),
+                    ],
+                },
+                vi5_cef::ParameterValue::Select("rounded".to_string()),
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_file() {
+        assert_golden(
+            "file",
+            &object_with(
+                vi5_cef::ParameterType::File {
+                    filters: vec!["*.png".to_string(), "*.jp,g".to_string()],
+                },
+                vi5_cef::ParameterValue::File(vi5_cef::FileValue {
+                    path: String::new(),
+                    modified_ms: 0,
+                }),
+            ),
+        );
+    }
+
+    fn component(step: vi5_cef::NumberStep, min: f64, max: f64) -> vi5_cef::NumberComponent {
+        vi5_cef::NumberComponent { step, min, max }
+    }
+
+    #[test]
+    fn golden_vector2() {
+        assert_golden(
+            "vector2",
+            &object_with(
+                vi5_cef::ParameterType::Vector2 {
+                    x: component(vi5_cef::NumberStep::One, -100.0, 100.0),
+                    y: component(vi5_cef::NumberStep::PointOne, 0.0, 10.0),
+                },
+                vi5_cef::ParameterValue::Vector2 { x: 5.0, y: 2.5 },
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_vector3() {
+        let axis = component(vi5_cef::NumberStep::PointZeroOne, -1.0, 1.0);
+        assert_golden(
+            "vector3",
+            &object_with(
+                vi5_cef::ParameterType::Vector3 {
+                    x: axis,
+                    y: axis,
+                    z: axis,
+                },
+                vi5_cef::ParameterValue::Vector3 {
+                    x: 0.0,
+                    y: 0.5,
+                    z: -1.0,
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn golden_range() {
+        let bound = component(vi5_cef::NumberStep::One, 0.0, 100.0);
+        assert_golden(
+            "range",
+            &object_with(
+                vi5_cef::ParameterType::Range {
+                    start: bound,
+                    end: bound,
+                },
+                vi5_cef::ParameterValue::Range {
+                    start: 10.0,
+                    end: 90.0,
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn packs_components_into_a_table() {
+        let object = object_with(
+            vi5_cef::ParameterType::Range {
+                start: component(vi5_cef::NumberStep::One, 0.0, 1.0),
+                end: component(vi5_cef::NumberStep::One, 0.0, 1.0),
+            },
+            vi5_cef::ParameterValue::Range {
+                start: 0.0,
+                end: 1.0,
+            },
+        );
+        let generated = generate("test", "vi5_aux2", &object).unwrap();
+        assert!(
+            generated
+                .content
+                .contains("{VI5_AUX2_value_start,VI5_AUX2_value_end}")
+        );
+        assert!(generated.content.contains(r#""Range""#));
+    }
+
+    #[test]
+    fn rejects_conflicting_component_variables() {
+        let mut object = object_with(
+            vi5_cef::ParameterType::Vector2 {
+                x: component(vi5_cef::NumberStep::One, 0.0, 1.0),
+                y: component(vi5_cef::NumberStep::One, 0.0, 1.0),
+            },
+            vi5_cef::ParameterValue::Vector2 { x: 0.0, y: 0.0 },
+        );
+        object
+            .parameter_definitions
+            .push(vi5_cef::ParameterDefinition {
+                key: "value_x".to_string(),
+                parameter_type: vi5_cef::ParameterType::Boolean,
+                default_value: None,
+                ..object.parameter_definitions[0].clone()
+            });
+        assert!(generate("test", "vi5_aux2", &object).is_err());
+    }
+
+    #[test]
+    fn golden_groups() {
+        let mut object = object_with(
+            vi5_cef::ParameterType::Boolean,
+            vi5_cef::ParameterValue::Bool(false),
+        );
+        let template = object.parameter_definitions[0].clone();
+        object.parameter_definitions = [
+            (
+                "outline",
+                Some("Style"),
+                None,
+                Some("Draw an outline,\naround the shape"),
+            ),
+            ("size", Some("Shape"), Some(1), None),
+            ("visible", None, None, Some("Hide the object: --track@x")),
+            ("fill", Some("Style"), Some(2), Some("  ")),
+            ("rounded", Some("Shape"), Some(0), None),
+        ]
+        .into_iter()
+        .map(
+            |(key, group, order, description)| vi5_cef::ParameterDefinition {
+                key: key.to_string(),
+                label: key.to_string(),
+                group: group.map(str::to_string),
+                description: description.map(str::to_string),
+                order,
+                ..template.clone()
+            },
+        )
+        .collect();
+        assert_golden("groups", &object);
+    }
+
+    #[test]
+    fn rejects_builtin_group_names() {
+        for group in ["Export", "performance", " Debug "] {
+            let mut object = object_with(
+                vi5_cef::ParameterType::Boolean,
+                vi5_cef::ParameterValue::Bool(false),
+            );
+            object.parameter_definitions[0].group = Some(group.to_string());
+            assert!(
+                generate("test", "vi5_aux2", &object).is_err(),
+                "{:?}",
+                group
+            );
+        }
+    }
+
+    #[test]
+    fn golden_filter() {
+        let mut object = object_with(
+            vi5_cef::ParameterType::Boolean,
+            vi5_cef::ParameterValue::Bool(true),
+        );
+        object.kind = vi5_cef::ObjectKind::Filter;
+        assert_golden("filter", &object);
+        let generated = generate("test", "vi5_aux2", &object).unwrap();
+        assert_eq!(generated.file_name, "Test Object.anm2");
+        assert!(generated.content.contains("obj.getpixeldata"));
+    }
+
+    #[test]
+    fn escapes_labels() {
+        let mut object = object_with(
+            vi5_cef::ParameterType::Boolean,
+            vi5_cef::ParameterValue::Bool(false),
+        );
+        object.parameter_definitions[0].label = "a,b:c\nd\u{7}".to_string();
+        let generated = generate("p:r\\oj", "vi5_aux2", &object).unwrap();
+        let lines = generated.content.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "--check@VI5_AUX2_value:a，b：c d,false");
+        assert_eq!(lines[1], "--label:vi5.aux2\\p：r＼oj");
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        for key in ["", "1abc", "a-b", "a b", "キー", "a\nb"] {
+            let mut object = object_with(
+                vi5_cef::ParameterType::Boolean,
+                vi5_cef::ParameterValue::Bool(false),
+            );
+            object.parameter_definitions[0].key = key.to_string();
+            assert!(generate("test", "vi5_aux2", &object).is_err(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn escapes_lua_strings() {
+        assert_eq!(lua_string(r#"a"b\c"#), r#""a\"b\\c""#);
+        assert_eq!(lua_string("a\nb\u{1}"), r#""a\nb\001""#);
+    }
+
+    #[test]
+    fn sanitizes_file_names() {
+        assert_eq!(
+            sanitize_file_name("a/b\\c:d*e?f\"g<h>i|j"),
+            "a_b_c_d_e_f_g_h_i_j"
+        );
+        assert_eq!(sanitize_file_name("name. "), "name");
+        assert_eq!(sanitize_file_name("CON"), "_CON");
+        assert_eq!(sanitize_file_name("com1.txt"), "_com1.txt");
+        assert_eq!(sanitize_file_name(""), "_");
+        assert_eq!(sanitize_file_name("..."), "_");
+        assert_eq!(
+            sanitize_file_name(&"あ".repeat(200)).chars().count(),
+            MAX_FILE_NAME_CHARS
+        );
+    }
+}
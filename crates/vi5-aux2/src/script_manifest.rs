@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use aviutl2::log;
+
+const MANIFEST_FILE_NAME: &str = "vi5.aux2.manifest.json";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+    pub object_id: String,
+    // 書き込んだ内容のハッシュ。一致しなければユーザーが編集している
+    pub hash: String,
+}
+
+/// スクリプトフォルダに生成したスクリプトの一覧。
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ScriptManifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+    // vi5.aux2が生成していないが、ユーザーが残すことを選んだファイル
+    #[serde(default)]
+    pub kept: BTreeSet<String>,
+}
+
+/// 古くなったスクリプトの扱い。
+#[derive(Debug, Default)]
+pub struct StaleScripts {
+    // 生成したまま変更されていないので、そのまま削除してよい
+    pub unmodified: Vec<String>,
+    // 生成した後にユーザーが編集したので、アーカイブに移す
+    pub modified: Vec<String>,
+    // vi5.aux2が生成したものではないので、ユーザーに確認する
+    pub foreign: Vec<String>,
+}
+
+pub fn hash_content(content: &str) -> String {
+    blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+impl ScriptManifest {
+    pub fn load(script_dir: &std::path::Path) -> Self {
+        let path = script_dir.join(MANIFEST_FILE_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Failed to parse {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, script_dir: &std::path::Path) -> anyhow::Result<()> {
+        std::fs::write(
+            script_dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// 今回生成したスクリプトの一覧に、生成に失敗して前回のものを残したスクリプトを加える。
+    /// `current`は残すスクリプトも含めた、小文字にしたファイル名。
+    pub fn with_kept_entries(
+        &self,
+        mut generated: BTreeMap<String, ManifestEntry>,
+        current: &BTreeSet<String>,
+    ) -> BTreeMap<String, ManifestEntry> {
+        let generated_lowercase = generated
+            .keys()
+            .map(|file_name| file_name.to_lowercase())
+            .collect::<BTreeSet<_>>();
+        for (file_name, entry) in &self.files {
+            let lowercase = file_name.to_lowercase();
+            if current.contains(&lowercase) && !generated_lowercase.contains(&lowercase) {
+                generated.insert(file_name.clone(), entry.clone());
+            }
+        }
+        generated
+    }
+
+    /// `current`（小文字にしたファイル名）に含まれないスクリプトを探して分類する。
+    pub fn find_stale(
+        &self,
+        script_dir: &std::path::Path,
+        current: &BTreeSet<String>,
+    ) -> anyhow::Result<StaleScripts> {
+        let mut stale = StaleScripts::default();
+        for entry in std::fs::read_dir(script_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let is_script = std::path::Path::new(&file_name)
+                .extension()
+                .is_some_and(|extension| {
+                    crate::script_generator::SCRIPT_EXTENSIONS
+                        .iter()
+                        .any(|script_extension| extension.eq_ignore_ascii_case(script_extension))
+                });
+            if !is_script
+                || current.contains(&file_name.to_lowercase())
+                || self.kept.contains(&file_name)
+            {
+                continue;
+            }
+            match self.files.get(&file_name) {
+                Some(manifest_entry) => {
+                    let content = std::fs::read_to_string(entry.path()).unwrap_or_default();
+                    if hash_content(&content) == manifest_entry.hash {
+                        stale.unmodified.push(file_name);
+                    } else {
+                        stale.modified.push(file_name);
+                    }
+                }
+                None => stale.foreign.push(file_name),
+            }
+        }
+        Ok(stale)
+    }
+}
+
+fn archive_dir(script_dir: &std::path::Path) -> std::path::PathBuf {
+    let dir_name = script_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // Scriptフォルダの中に置くとAviUtl2に読み込まれてしまうので、外に移す
+    aviutl2::config::app_data_path()
+        .join("Plugin")
+        .join("vi5")
+        .join("archive")
+        .join(dir_name)
+        .join(timestamp.to_string())
+}
+
+/// スクリプトをアーカイブフォルダに移し、移動先のフォルダを返す。
+pub fn archive(
+    script_dir: &std::path::Path,
+    file_names: &[String],
+) -> anyhow::Result<std::path::PathBuf> {
+    let archive_dir = archive_dir(script_dir);
+    std::fs::create_dir_all(&archive_dir)?;
+    for file_name in file_names {
+        let from = script_dir.join(file_name);
+        let to = archive_dir.join(file_name);
+        // 別ドライブだとrenameできないので、コピーしてから消す
+        if std::fs::rename(&from, &to).is_err() {
+            std::fs::copy(&from, &to)?;
+            std::fs::remove_file(&from)?;
+        }
+        log::info!("Archived stale script {:?} to {:?}", from, to);
+    }
+    Ok(archive_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとに空のスクリプトフォルダを作る
+    fn script_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vi5-aux2-script-manifest-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_generated(dir: &std::path::Path, manifest: &mut ScriptManifest, file_name: &str) {
+        let content = format!("-- {}", file_name);
+        std::fs::write(dir.join(file_name), &content).unwrap();
+        manifest.files.insert(
+            file_name.to_string(),
+            ManifestEntry {
+                object_id: format!("/src/{}.object.ts", file_name),
+                hash: hash_content(&content),
+            },
+        );
+    }
+
+    #[test]
+    fn classifies_stale_scripts() {
+        let dir = script_dir("classify");
+        let mut manifest = ScriptManifest::default();
+        write_generated(&dir, &mut manifest, "Current.obj2");
+        write_generated(&dir, &mut manifest, "Unmodified.obj2");
+        write_generated(&dir, &mut manifest, "Modified.anm2");
+        std::fs::write(dir.join("Modified.anm2"), "-- edited").unwrap();
+        std::fs::write(dir.join("Foreign.obj2"), "-- foreign").unwrap();
+        std::fs::write(dir.join("Kept.obj2"), "-- kept").unwrap();
+        manifest.kept.insert("Kept.obj2".to_string());
+        // スクリプトでないファイルやフォルダは無視する
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::create_dir(dir.join("Folder.obj2")).unwrap();
+
+        let current = BTreeSet::from(["current.obj2".to_string()]);
+        let stale = manifest.find_stale(&dir, &current).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stale.unmodified, vec!["Unmodified.obj2".to_string()]);
+        assert_eq!(stale.modified, vec!["Modified.anm2".to_string()]);
+        assert_eq!(stale.foreign, vec!["Foreign.obj2".to_string()]);
+    }
+
+    #[test]
+    fn keeps_entries_of_scripts_that_failed_to_generate() {
+        let dir = script_dir("failed");
+        let mut previous = ScriptManifest::default();
+        write_generated(&dir, &mut previous, "Failed.obj2");
+        write_generated(&dir, &mut previous, "Updated.obj2");
+        write_generated(&dir, &mut previous, "Removed.obj2");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let updated = ManifestEntry {
+            object_id: "/src/Updated.object.ts".to_string(),
+            hash: hash_content("-- new"),
+        };
+        let generated = BTreeMap::from([("Updated.obj2".to_string(), updated.clone())]);
+        // Failed.obj2は生成に失敗したが、消さずに残している
+        let current = BTreeSet::from(["failed.obj2".to_string(), "updated.obj2".to_string()]);
+        let files = previous.with_kept_entries(generated, &current);
+
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec!["Failed.obj2", "Updated.obj2"]
+        );
+        assert_eq!(
+            files["Failed.obj2"].hash,
+            previous.files["Failed.obj2"].hash
+        );
+        assert_eq!(files["Updated.obj2"].hash, updated.hash);
+    }
+
+    #[test]
+    fn finds_nothing_when_all_scripts_are_current() {
+        let dir = script_dir("current");
+        let mut manifest = ScriptManifest::default();
+        write_generated(&dir, &mut manifest, "A.obj2");
+        write_generated(&dir, &mut manifest, "B.anm2");
+
+        let current = BTreeSet::from(["a.obj2".to_string(), "b.anm2".to_string()]);
+        let stale = manifest.find_stale(&dir, &current).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(stale.unmodified.is_empty());
+        assert!(stale.modified.is_empty());
+        assert!(stale.foreign.is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::sync::{Arc, Mutex};
+
+use crate::lua_call::{LuaCall, LuaParameter};
+
+const FRAMERATE: f64 = 30.0;
+const CURRENT_FRAME: i32 = 5;
+const START_FRAME: i32 = 100;
+
+fn object_info() -> vi5_cef::ObjectInfo {
+    vi5_cef::ObjectInfo {
+        id: "/src/test.object.ts".to_string(),
+        label: "Test".to_string(),
+        parameter_definitions: vec![
+            vi5_cef::ParameterDefinition {
+                key: "speed".to_string(),
+                parameter_type: vi5_cef::ParameterType::Number {
+                    step: vi5_cef::NumberStep::PointOne,
+                    min: 0.0,
+                    max: 100.0,
+                },
+                label: "Speed".to_string(),
+                default_value: None,
+                group: None,
+                description: None,
+                order: None,
+            },
+            vi5_cef::ParameterDefinition {
+                key: "name".to_string(),
+                parameter_type: vi5_cef::ParameterType::String,
+                label: "Name".to_string(),
+                default_value: None,
+                group: None,
+                description: None,
+                order: None,
+            },
+            vi5_cef::ParameterDefinition {
+                key: "offset".to_string(),
+                parameter_type: vi5_cef::ParameterType::Vector2 {
+                    x: vi5_cef::NumberComponent {
+                        step: vi5_cef::NumberStep::One,
+                        min: -100.0,
+                        max: 100.0,
+                    },
+                    y: vi5_cef::NumberComponent {
+                        step: vi5_cef::NumberStep::One,
+                        min: -100.0,
+                        max: 100.0,
+                    },
+                },
+                label: "Offset".to_string(),
+                default_value: None,
+                group: None,
+                description: None,
+                order: None,
+            },
+            vi5_cef::ParameterDefinition {
+                key: "tint".to_string(),
+                parameter_type: vi5_cef::ParameterType::Color,
+                label: "Tint".to_string(),
+                default_value: None,
+                group: None,
+                description: None,
+                order: None,
+            },
+        ],
+        stateful: false,
+        version: String::new(),
+        dependencies: None,
+        kind: vi5_cef::ObjectKind::Generator,
+    }
+}
+
+fn filter_object_info() -> vi5_cef::ObjectInfo {
+    vi5_cef::ObjectInfo {
+        kind: vi5_cef::ObjectKind::Filter,
+        ..object_info()
+    }
+}
+
+struct ScriptRun {
+    call: LuaCall,
+    // set_input_imageに渡された(effect_id, width, height)
+    input_images: Vec<(i32, usize, usize)>,
+}
+
+// トラックバーと座標が時刻に比例して動くオブジェクトとしてスクリプトを実行し、call_objectに渡された引数を返す
+fn run_script(object_info: &vi5_cef::ObjectInfo, batch_size: i32) -> ScriptRun {
+    let script = crate::script_generator::generate("test", "vi5_aux2", object_info)
+        .unwrap()
+        .content;
+    let lua = mlua::Lua::new();
+    let payload = Arc::new(Mutex::new(None));
+    let input_images = Arc::new(Mutex::new(Vec::new()));
+
+    let internal = lua.create_table().unwrap();
+    internal
+        .set(
+            "call_object",
+            lua.create_function({
+                let payload = payload.clone();
+                move |_, value: String| {
+                    *payload.lock().unwrap() = Some(value);
+                    Ok((mlua::Value::Nil, 1, 1))
+                }
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    internal
+        .set(
+            "set_input_image",
+            lua.create_function({
+                let input_images = input_images.clone();
+                move |_, (effect_id, _, width, height): (i32, mlua::Value, usize, usize)| {
+                    input_images
+                        .lock()
+                        .unwrap()
+                        .push((effect_id, width, height));
+                    Ok(())
+                }
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    internal
+        .set(
+            "free_image",
+            lua.create_function(|_, _: i32| Ok(())).unwrap(),
+        )
+        .unwrap();
+    let internal = mlua::Value::Table(internal);
+
+    let obj = lua.create_table().unwrap();
+    obj.set(
+        "module",
+        lua.create_function(move |_, _: String| Ok(internal.clone()))
+            .unwrap(),
+    )
+    .unwrap();
+    obj.set(
+        "getvalue",
+        lua.create_function(|_, (target, time): (String, Option<f64>)| {
+            let time = time.unwrap_or(CURRENT_FRAME as f64 / FRAMERATE);
+            Ok(match target.as_str() {
+                "frame_s" => Some(START_FRAME as f64),
+                "x" => Some(time * 10.0),
+                "y" => Some(time * 20.0),
+                "z" => Some(time * 30.0),
+                "track.VI5_AUX2_speed" => Some(time * FRAMERATE),
+                // yは取れないので、現在のフレームの値が使われる
+                "track.VI5_AUX2_offset_x" => Some(time * FRAMERATE * 2.0),
+                _ => None,
+            })
+        })
+        .unwrap(),
+    )
+    .unwrap();
+    obj.set(
+        "getinfo",
+        lua.create_function(|_, _: String| Ok(false)).unwrap(),
+    )
+    .unwrap();
+    obj.set(
+        "getpixeldata",
+        lua.create_function(|_, _: mlua::MultiValue| Ok((mlua::Value::Nil, 16, 9)))
+            .unwrap(),
+    )
+    .unwrap();
+    obj.set(
+        "putpixeldata",
+        lua.create_function(|_, _: mlua::MultiValue| Ok(()))
+            .unwrap(),
+    )
+    .unwrap();
+    obj.set("effect_id", 1).unwrap();
+    obj.set("frame", CURRENT_FRAME).unwrap();
+    obj.set("time", CURRENT_FRAME as f64 / FRAMERATE).unwrap();
+    obj.set("framerate", FRAMERATE).unwrap();
+    obj.set("totalframe", 60).unwrap();
+    obj.set("totaltime", 2.0).unwrap();
+    obj.set("screen_w", 1920).unwrap();
+    obj.set("screen_h", 1080).unwrap();
+
+    let globals = lua.globals();
+    globals.set("obj", obj).unwrap();
+    globals.set("batch_size", batch_size).unwrap();
+    globals.set("seed", 0).unwrap();
+    globals.set("freeze", false).unwrap();
+    globals.set("error_image", true).unwrap();
+    // 現在のフレームでのトラックバーの値
+    globals.set("VI5_AUX2_speed", CURRENT_FRAME as f64).unwrap();
+    globals.set("VI5_AUX2_name", "a,\"b\"\n:c").unwrap();
+    globals
+        .set("VI5_AUX2_offset_x", CURRENT_FRAME as f64 * 2.0)
+        .unwrap();
+    globals.set("VI5_AUX2_offset_y", 7.0).unwrap();
+    globals.set("VI5_AUX2_tint", 0x112233).unwrap();
+    globals.set("VI5_AUX2_tint_alpha", 127.6).unwrap();
+
+    lua.load(&script).exec().unwrap();
+    let payload = payload
+        .lock()
+        .unwrap()
+        .take()
+        .expect("call_object was not called");
+    let input_images = input_images.lock().unwrap().clone();
+    ScriptRun {
+        call: LuaCall::decode(&payload).unwrap(),
+        input_images,
+    }
+}
+
+#[test]
+fn samples_tracks_and_position_at_each_batch_frame() {
+    let call = run_script(&object_info(), 4).call;
+    assert_eq!(call.frames.len(), 4);
+    call.check_schema(&object_info()).unwrap();
+
+    for (offset, frame) in call.frames.iter().enumerate() {
+        let current_frame = CURRENT_FRAME + offset as i32;
+        let time = current_frame as f64 / FRAMERATE;
+        let frame_info = &frame.frame_info;
+        assert_eq!(frame_info.current_frame, current_frame);
+        assert!((frame_info.current_time - time).abs() < 1e-9);
+        assert_eq!(frame_info.global_frame, START_FRAME + current_frame);
+        assert!((frame_info.x - time * 10.0).abs() < 1e-9);
+        assert!((frame_info.y - time * 20.0).abs() < 1e-9);
+        assert!((frame_info.z - time * 30.0).abs() < 1e-9);
+
+        match frame.parameters.get("speed") {
+            Some(LuaParameter::Number(speed)) => {
+                assert!(
+                    (speed - current_frame as f64).abs() < 1e-9,
+                    "frame {}: speed was {}",
+                    current_frame,
+                    speed
+                );
+            }
+            other => panic!("unexpected speed: {:?}", other),
+        }
+        match frame.parameters.get("name") {
+            Some(LuaParameter::Str(name)) => assert_eq!(name, "a,\"b\"\n:c"),
+            other => panic!("unexpected name: {:?}", other),
+        }
+        match frame.parameters.get("offset") {
+            Some(&LuaParameter::Vector2(x, y)) => {
+                assert!((x - current_frame as f64 * 2.0).abs() < 1e-9, "x was {}", x);
+                assert_eq!(y, 7.0);
+            }
+            other => panic!("unexpected offset: {:?}", other),
+        }
+        match frame.parameters.get("tint") {
+            Some(LuaParameter::Color(tint)) => assert_eq!(*tint, 0x80112233),
+            other => panic!("unexpected tint: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn stops_batch_at_the_end_of_the_object() {
+    let call = run_script(&object_info(), 100).call;
+    assert_eq!(call.frames.len(), 60 - CURRENT_FRAME as usize);
+}
+
+#[test]
+fn filter_renders_only_the_current_frame_with_input_image() {
+    let run = run_script(&filter_object_info(), 4);
+    assert_eq!(run.input_images, vec![(1, 16, 9)]);
+
+    let call = run.call;
+    call.check_schema(&filter_object_info()).unwrap();
+    assert_eq!(call.frames.len(), 1);
+    let frame = &call.frames[0];
+    assert_eq!(frame.frame_info.current_frame, CURRENT_FRAME);
+    match frame.parameters.get("speed") {
+        Some(LuaParameter::Number(speed)) => assert!((speed - CURRENT_FRAME as f64).abs() < 1e-9),
+        other => panic!("unexpected speed: {:?}", other),
+    }
+}
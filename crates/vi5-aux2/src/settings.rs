@@ -0,0 +1,89 @@
+use aviutl2::log;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// レンダリング結果をメモリに保持する上限（MiB）
+    pub render_cache_budget_mb: usize,
+    /// フリーズしたオブジェクトのレンダリング結果をディスクに保持する上限（MiB）
+    pub disk_cache_budget_mb: usize,
+    /// 表示したフレームの後に先読みするフレーム数（0で無効）
+    pub prefetch_frames: usize,
+    /// プレビュー時に1回のバッチ描画にかけてよい時間（ミリ秒）
+    pub preview_latency_budget_ms: u64,
+    /// プレビュー時に描画を待たず、前回の画像を表示しておいて描画が終わったら再描画する
+    pub non_blocking_preview: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            render_cache_budget_mb: 1024,
+            disk_cache_budget_mb: 4096,
+            prefetch_frames: 30,
+            preview_latency_budget_ms: 100,
+            non_blocking_preview: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn render_cache_budget(&self) -> usize {
+        self.render_cache_budget_mb * 1024 * 1024
+    }
+
+    pub fn disk_cache_budget(&self) -> u64 {
+        self.disk_cache_budget_mb as u64 * 1024 * 1024
+    }
+}
+
+static SETTINGS: std::sync::LazyLock<std::sync::RwLock<Settings>> =
+    std::sync::LazyLock::new(|| std::sync::RwLock::new(load()));
+
+pub fn settings_path() -> std::path::PathBuf {
+    aviutl2::config::app_data_path()
+        .join("Plugin")
+        .join("vi5")
+        .join("vi5.aux2.json")
+}
+
+pub fn get() -> Settings {
+    SETTINGS.read().unwrap().clone()
+}
+
+fn load() -> Settings {
+    let path = settings_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::error!("Failed to parse {}: {}", path.display(), e);
+                Settings::default()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+        Err(e) => {
+            log::error!("Failed to read {}: {}", path.display(), e);
+            Settings::default()
+        }
+    }
+}
+
+pub fn reload() -> Settings {
+    let settings = load();
+    log::info!("Loaded settings: {:?}", settings);
+    *SETTINGS.write().unwrap() = settings.clone();
+    settings
+}
+
+/// 設定ファイルがなければ現在の設定で作成し、そのパスを返す。
+pub fn ensure_file() -> anyhow::Result<std::path::PathBuf> {
+    let path = settings_path();
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&get())?)?;
+    }
+    Ok(path)
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// クライアントからのハートビートで更新されるリース。
+/// 猶予期間内にハートビートが来なければ、所有者がいなくなったとみなす。
+pub struct Lease {
+    grace_period: Duration,
+    last_heartbeat: std::sync::Mutex<Instant>,
+}
+
+impl Lease {
+    pub fn new(grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            last_heartbeat: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    pub fn renew(&self) {
+        *self
+            .last_heartbeat
+            .lock()
+            .expect("Failed to lock lease state") = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.last_heartbeat
+            .lock()
+            .expect("Failed to lock lease state")
+            .elapsed()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.elapsed() > self.grace_period
+    }
+}
+
+pub async fn watch_lease(lease: Arc<Lease>, sender: Arc<tokio::sync::mpsc::UnboundedSender<()>>) {
+    let check_interval = (lease.grace_period() / 4).max(Duration::from_secs(1));
+    loop {
+        if sender.is_closed() {
+            break;
+        }
+        if lease.is_expired() {
+            tracing::info!(
+                "No heartbeat received for {:?}, shutting down.",
+                lease.elapsed()
+            );
+            let _ = sender.send(());
+            break;
+        }
+        tokio::time::sleep(check_interval).await;
+    }
+}
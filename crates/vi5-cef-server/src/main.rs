@@ -1,9 +1,12 @@
 mod cef_app;
 mod gpu_capture;
 mod handlers;
+mod lease;
 mod protocol;
 mod render_loop;
 mod server;
+mod shutdown;
+mod transport;
 mod types;
 
 use std::sync::Arc;
@@ -33,9 +36,25 @@ pub struct Args {
     #[clap(long, default_value = "50051")]
     port: u16,
 
+    /// Unix domain socket path / Windows named pipe name to listen on instead of the port
+    #[clap(long)]
+    local_socket: Option<String>,
+
+    /// Shared secret that clients must send as a bearer token
+    #[clap(long, env = "VI5_CEF_SERVER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
     /// Parent process (will exit if parent process exits)
     #[clap(long)]
     parent_process: Option<u32>,
+
+    /// Seconds to wait for a client heartbeat before exiting (0 to disable)
+    #[clap(long, default_value = "0")]
+    lease_grace_period: u64,
+
+    /// Seconds to wait for in-flight renders when shutting down
+    #[clap(long, default_value = "10")]
+    shutdown_deadline: u64,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -87,8 +106,15 @@ fn main() -> anyhow::Result<()> {
         .build()?
         .block_on(main_server(
             render_loop,
-            cli_args.port,
+            match cli_args.local_socket {
+                Some(path) => transport::ListenAddress::Local(path),
+                None => transport::ListenAddress::Tcp(cli_args.port),
+            },
+            cli_args.token,
             cli_args.parent_process,
+            (cli_args.lease_grace_period > 0)
+                .then(|| std::time::Duration::from_secs(cli_args.lease_grace_period)),
+            std::time::Duration::from_secs(cli_args.shutdown_deadline),
         ))?;
     Ok(())
 }
@@ -114,8 +140,11 @@ async fn watch_parent_process(
 
 pub async fn main_server(
     render_loop: RenderLoop,
-    port: u16,
+    listen: transport::ListenAddress,
+    token: Option<String>,
     parent_pid: Option<u32>,
+    lease_grace_period: Option<std::time::Duration>,
+    shutdown_deadline: std::time::Duration,
 ) -> anyhow::Result<()> {
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::unbounded_channel();
     let shutdown_tx = Arc::new(shutdown_tx);
@@ -125,34 +154,74 @@ pub async fn main_server(
             watch_parent_process(ppid, shutdown_tx_clone).await;
         });
     }
-    let server = server::MainServer::new(render_loop, shutdown_tx);
-    let addr = format!("[::1]:{}", port).parse().unwrap();
-    tracing::info!("Starting gRPC server on {}", addr);
-    tonic::transport::Server::builder()
+    let lease = lease_grace_period.map(|grace_period| Arc::new(lease::Lease::new(grace_period)));
+    if let Some(lease) = &lease {
+        tracing::info!(
+            "Lease enabled with grace period of {:?}",
+            lease.grace_period()
+        );
+        let lease = lease.clone();
+        let shutdown_tx_clone = shutdown_tx.clone();
+        tokio::spawn(async move {
+            lease::watch_lease(lease, shutdown_tx_clone).await;
+        });
+    }
+    let server = Arc::new(server::MainServer::new(
+        render_loop,
+        shutdown_tx,
+        lease,
+        shutdown_deadline,
+    ));
+    if token.is_none() {
+        tracing::warn!("No token is set, any local process can connect to the server");
+    }
+    tracing::info!("Starting gRPC server on {}", listen);
+    let auth_interceptor = transport::AuthInterceptor::new(token);
+    let router = tonic::transport::Server::builder()
         .max_frame_size((1 << 24) - 1)
-        .add_service(
-            crate::protocol::libserver::lib_server_server::LibServerServer::new(server)
-                .max_decoding_message_size(usize::MAX),
-        )
-        .add_service(
+        .add_service(tonic::service::interceptor::InterceptedService::new(
+            crate::protocol::libserver::lib_server_server::LibServerServer::from_arc(
+                server.clone(),
+            )
+            .max_decoding_message_size(usize::MAX),
+            auth_interceptor.clone(),
+        ))
+        // リフレクションでもAPIの一覧が見えてしまうので、同じトークンを要求する
+        .add_service(tonic::service::interceptor::InterceptedService::new(
             tonic_reflection::server::Builder::configure()
                 .register_encoded_file_descriptor_set(
                     crate::protocol::libserver::FILE_DESCRIPTOR_SET,
                 )
                 .build_v1()
                 .unwrap(),
-        )
-        .serve_with_shutdown(addr, async {
-            tokio::select! {
-                _ = tokio::signal::ctrl_c() => {
-                    tracing::info!("Shutting down gRPC server");
-                }
-                _ = shutdown_rx.recv() => {
-                    tracing::info!("Received shutdown request");
-                }
+            auth_interceptor,
+        ));
+    let shutdown_signal = async {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                tracing::info!("Shutting down gRPC server");
+            }
+            _ = shutdown_rx.recv() => {
+                tracing::info!("Received shutdown request");
             }
-        })
-        .await?;
+        }
+        server
+            .graceful_shutdown(server.default_shutdown_deadline())
+            .await;
+    };
+    match &listen {
+        transport::ListenAddress::Tcp(port) => {
+            let addr = format!("[::1]:{}", port).parse().unwrap();
+            router.serve_with_shutdown(addr, shutdown_signal).await?;
+        }
+        transport::ListenAddress::Local(path) => {
+            let result = router
+                .serve_with_incoming_shutdown(transport::local_incoming(path)?, shutdown_signal)
+                .await;
+            transport::remove_local_socket(path);
+            result?;
+        }
+    }
 
     Ok(())
 }
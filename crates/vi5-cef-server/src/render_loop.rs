@@ -2,7 +2,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use base64::Engine;
-use cef::{ImplBrowser, ImplFrame};
+use cef::{ImplBrowser, ImplBrowserHost, ImplFrame};
 use prost::Message;
 use tokio::sync::broadcast;
 
@@ -91,6 +91,38 @@ fn on_paint(buffer: &[u8], width: usize, height: usize, bytes_per_row: usize) {
     }
 }
 
+// ページに中身を送ったファイルの(パス, 更新日時)
+type DeliveredFiles = std::collections::HashSet<(String, i64)>;
+
+/// ファイルパラメーターの中身を読んでリクエストに入れ、ページから読めるようにする。
+///
+/// ページ側はパスと更新日時ごとにキャッシュするので、ページを読み込み直すまではそれぞれ最初の1回だけ入れる。
+fn inline_files(
+    request: &mut crate::protocol::common::BatchRenderRequest,
+    delivered: &mut DeliveredFiles,
+) {
+    for render_request in &mut request.render_requests {
+        for parameter in &mut render_request.parameters {
+            let Some(crate::protocol::common::parameter::Value::FileValue(file)) =
+                &mut parameter.value
+            else {
+                continue;
+            };
+            if file.path.is_empty() || !delivered.insert((file.path.clone(), file.modified_ms)) {
+                continue;
+            }
+            match std::fs::read(&file.path) {
+                Ok(data) => file.data = data,
+                Err(e) => {
+                    tracing::warn!("Failed to read file parameter {}: {}", file.path, e);
+                    // 次のバッチでもう一度読む
+                    delivered.remove(&(file.path.clone(), file.modified_ms));
+                }
+            }
+        }
+    }
+}
+
 pub fn on_software_paint(buffer: &[u8], width: usize, height: usize) {
     tracing::debug!("Software paint received: {}x{}", width, height);
     on_paint(buffer, width, height, width * 4);
@@ -111,6 +143,7 @@ pub struct RenderLoop {
         Arc<std::sync::Mutex<Option<anyhow::Result<crate::protocol::serverjs::InitializeInfo>>>>,
     notification_tx: broadcast::Sender<crate::protocol::libserver::Notification>,
     notification_history: Arc<std::sync::Mutex<Vec<crate::protocol::libserver::Notification>>>,
+    delivered_files: Arc<std::sync::Mutex<DeliveredFiles>>,
 }
 
 impl RenderLoop {
@@ -121,6 +154,7 @@ impl RenderLoop {
             initialized: Arc::new(std::sync::Mutex::new(None)),
             notification_tx,
             notification_history: Arc::new(std::sync::Mutex::new(Vec::new())),
+            delivered_files: Arc::new(std::sync::Mutex::new(DeliveredFiles::new())),
         }
     }
 
@@ -185,6 +219,10 @@ impl RenderLoop {
             .lock()
             .expect("Failed to lock notification history")
             .clear();
+        self.delivered_files
+            .lock()
+            .expect("Failed to lock delivered files")
+            .clear();
         PAINT_CALLBACKS.clear();
         PAINT_CALLBACKS.insert(
             NOTIFICATION_NONCE,
@@ -251,7 +289,13 @@ impl RenderLoop {
             0,
             Box::new({
                 let initialized = self.initialized.clone();
+                let delivered_files = self.delivered_files.clone();
                 move |buffer, _, _| {
+                    // ページは読み込み直すたびに初期化のメッセージを描くので、それまでに送ったファイルは忘れる
+                    delivered_files
+                        .lock()
+                        .expect("Failed to lock delivered files")
+                        .clear();
                     let result = match read_message_from_image::<
                         crate::protocol::serverjs::InitializeInfo,
                     >(buffer)
@@ -273,8 +317,11 @@ impl RenderLoop {
                         .expect("Failed to lock initialization state");
                     if initialized.is_none() {
                         *initialized = Some(result);
+                    } else {
+                        tracing::info!("Page was reloaded");
                     }
-                    std::ops::ControlFlow::Break(())
+                    // ホットリロードでも再び描かれるので、コールバックは残しておく
+                    std::ops::ControlFlow::Continue(())
                 }
             }),
         );
@@ -297,6 +344,7 @@ impl RenderLoop {
     pub async fn batch_render(
         &self,
         request: crate::protocol::common::BatchRenderRequest,
+        ticket: &crate::shutdown::RenderTicket,
     ) -> anyhow::Result<crate::protocol::libserver::BatchRenderResponse> {
         self.assert_initialized().await?;
         if request.render_requests.is_empty() {
@@ -384,6 +432,11 @@ impl RenderLoop {
                                         width: renderered_object_info.width,
                                         height: renderered_object_info.height,
                                         image_data,
+                                        // CEFのOnPaint / OnAcceleratedPaint（cef_render_handler.h）が渡すバッファは、
+                                        // Chromiumのコンポジタが出力したもの（SkBitmapのkPremul_SkAlphaType）を
+                                        // そのまま写したものなので、アルファ乗算済みのsRGBになっている
+                                        alpha_mode: crate::protocol::libserver::AlphaMode::Premultiplied as _,
+                                        color_space: crate::protocol::libserver::ColorSpace::Srgb as _,
                                     },
                                 ),
                             ),
@@ -416,12 +469,21 @@ impl RenderLoop {
             std::ops::ControlFlow::Continue(())
         });
         PAINT_CALLBACKS.insert(nonce, callback);
+        let mut request = request;
+        inline_files(
+            &mut request,
+            &mut self
+                .delivered_files
+                .lock()
+                .expect("Failed to lock delivered files"),
+        );
         let request = base64::engine::general_purpose::STANDARD.encode(request.encode_to_vec());
         let js = format!("window.__vi5__.render({nonce}, '{request}');");
+        // 入力画像やファイルの中身が入ると巨大になるので、中身はログに出さない
         tracing::debug!(
-            "Executing JS to request frame with nonce {}: {}",
+            "Executing JS to request frame with nonce {} ({} bytes)",
             nonce,
-            &js
+            js.len()
         );
         tracing::debug!("Requesting frame with nonce {}", nonce);
         self.browser.main_frame().unwrap().execute_java_script(
@@ -450,6 +512,10 @@ impl RenderLoop {
                         PAINT_CALLBACKS.remove(&nonce);
                         anyhow::bail!("Timeout waiting for render responses");
                     }
+                    if ticket.is_cancelled() {
+                        PAINT_CALLBACKS.remove(&nonce);
+                        anyhow::bail!("Render was cancelled");
+                    }
                 }
                 Ok(response) => {
                     render_responses.push(response?);
@@ -479,6 +545,19 @@ impl RenderLoop {
         );
         Ok(())
     }
+
+    pub async fn close(&self) {
+        tracing::info!("Closing browser");
+        PAINT_CALLBACKS.clear();
+        if let Some(host) = self.browser.host() {
+            host.close_browser(1);
+        }
+        // CEFに後始末をさせるため、しばらくメッセージループを回す
+        for _ in 0..10 {
+            cef::do_message_loop_work();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
 }
 
 fn publish_notification(
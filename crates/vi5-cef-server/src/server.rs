@@ -8,6 +8,10 @@ pub struct MainServer {
     render_loop: crate::render_loop::RenderLoop,
     processes: tokio::sync::Mutex<Vec<tokio::process::Child>>,
     shutdown_tx: tokio::sync::Mutex<Option<Arc<tokio::sync::mpsc::UnboundedSender<()>>>>,
+    lease: Option<Arc<crate::lease::Lease>>,
+    render_tracker: Arc<crate::shutdown::RenderTracker>,
+    default_shutdown_deadline: std::time::Duration,
+    shutdown_report: tokio::sync::OnceCell<crate::shutdown::ShutdownReport>,
 }
 
 #[tonic::async_trait]
@@ -58,6 +62,9 @@ impl crate::protocol::libserver::lib_server_server::LibServer for MainServer {
                 .arg("--port")
                 .arg(random_port.to_string())
                 .current_dir(path)
+                // nodeやそこから起動されるプロセスにはトークンを渡さない
+                .env_remove("VI5_CEF_SERVER_TOKEN")
+                .kill_on_drop(true)
                 .spawn()
                 .map_err(|e| {
                     tonic::Status::internal(format!("Failed to start vi5 process: {}", e))
@@ -98,12 +105,28 @@ impl crate::protocol::libserver::lib_server_server::LibServer for MainServer {
     ) -> Result<tonic::Response<crate::protocol::libserver::BatchRenderResponse>, tonic::Status>
     {
         let req = request.into_inner();
-        tracing::info!("Received batch render request: {:?}", req);
-        let render_results = self
-            .render_loop
-            .batch_render(req)
-            .await
-            .map_err(|e| tonic::Status::internal(format!("Batch render failed: {}", e)))?;
+        tracing::info!(
+            "Received batch render request with {} requests",
+            req.render_requests.len()
+        );
+        let Some(ticket) = self.render_tracker.begin() else {
+            return Err(tonic::Status::unavailable("Server is shutting down"));
+        };
+        // 途中でFutureを捨てるとJS側の描画やコールバックが残るので、打ち切りはrender_loopに任せる
+        let render_results = match self.render_loop.batch_render(req, &ticket).await {
+            Ok(render_results) => render_results,
+            Err(_) if ticket.is_cancelled() => {
+                return Err(tonic::Status::cancelled(
+                    "Batch render was cancelled because the server is shutting down",
+                ));
+            }
+            Err(e) => {
+                return Err(tonic::Status::internal(format!(
+                    "Batch render failed: {}",
+                    e
+                )));
+            }
+        };
         Ok(tonic::Response::new(render_results))
     }
 
@@ -140,13 +163,45 @@ impl crate::protocol::libserver::lib_server_server::LibServer for MainServer {
 
     async fn shutdown(
         &self,
-        _request: tonic::Request<crate::protocol::common::Void>,
-    ) -> Result<tonic::Response<crate::protocol::common::Void>, tonic::Status> {
-        tracing::info!("Received shutdown request");
+        request: tonic::Request<crate::protocol::libserver::ShutdownRequest>,
+    ) -> Result<tonic::Response<crate::protocol::libserver::ShutdownResponse>, tonic::Status> {
+        let req = request.into_inner();
+        tracing::info!("Received shutdown request: {:?}", req);
+        let deadline = if req.deadline_ms == 0 {
+            self.default_shutdown_deadline
+        } else {
+            std::time::Duration::from_millis(req.deadline_ms)
+        };
+        let report = self.graceful_shutdown(deadline).await;
         if let Some(tx) = self.shutdown_tx.lock().await.take() {
             let _ = tx.send(());
         }
-        Ok(tonic::Response::new(crate::protocol::common::Void {}))
+        Ok(tonic::Response::new(
+            crate::protocol::libserver::ShutdownResponse {
+                drained_renders: report.drained_renders as _,
+                cancelled_renders: report.cancelled_renders as _,
+                timed_out: report.timed_out,
+            },
+        ))
+    }
+
+    async fn heartbeat(
+        &self,
+        _request: tonic::Request<crate::protocol::common::Void>,
+    ) -> Result<tonic::Response<crate::protocol::libserver::HeartbeatResponse>, tonic::Status> {
+        tracing::trace!("Received heartbeat");
+        let lease_grace_period_ms = match &self.lease {
+            Some(lease) => {
+                lease.renew();
+                lease.grace_period().as_millis() as u64
+            }
+            None => 0,
+        };
+        Ok(tonic::Response::new(
+            crate::protocol::libserver::HeartbeatResponse {
+                lease_grace_period_ms,
+            },
+        ))
     }
 }
 
@@ -154,33 +209,59 @@ impl MainServer {
     pub fn new(
         render_loop: crate::render_loop::RenderLoop,
         shutdown_tx: Arc<tokio::sync::mpsc::UnboundedSender<()>>,
+        lease: Option<Arc<crate::lease::Lease>>,
+        default_shutdown_deadline: std::time::Duration,
     ) -> Self {
         Self {
             render_loop,
             processes: tokio::sync::Mutex::new(Vec::new()),
             shutdown_tx: tokio::sync::Mutex::new(Some(shutdown_tx)),
+            lease,
+            render_tracker: Arc::new(crate::shutdown::RenderTracker::new()),
+            default_shutdown_deadline,
+            shutdown_report: tokio::sync::OnceCell::new(),
         }
     }
-}
 
-impl Drop for MainServer {
-    fn drop(&mut self) {
-        for mut process in futures::executor::block_on(self.processes.lock()).drain(..) {
-            match futures::executor::block_on(process.kill()) {
-                Ok(_) => {
-                    tracing::info!(
-                        "Successfully killed vi5 process with PID: {}",
-                        process.id().unwrap_or(0)
-                    );
+    pub fn default_shutdown_deadline(&self) -> std::time::Duration {
+        self.default_shutdown_deadline
+    }
+
+    /// レンダリングの受付停止 -> 処理中のレンダリングの完了待ち（またはキャンセル）
+    /// -> JS側のキャッシュ破棄 -> vi5プロセスの停止 -> ブラウザのクローズ、の順に終了処理を行う。
+    /// 二回目以降の呼び出しでは最初の結果を返す。
+    pub async fn graceful_shutdown(
+        &self,
+        deadline: std::time::Duration,
+    ) -> crate::shutdown::ShutdownReport {
+        *self
+            .shutdown_report
+            .get_or_init(|| async {
+                let report = self.render_tracker.drain(deadline).await;
+                if let Err(e) = self.render_loop.purge_cache().await {
+                    tracing::warn!("Failed to purge cache during shutdown: {}", e);
                 }
-                Err(e) => {
-                    tracing::error!(
-                        "Failed to kill vi5 process with PID: {}: {}",
-                        process.id().unwrap_or(0),
-                        e
-                    );
+                for mut process in self.processes.lock().await.drain(..) {
+                    match process.kill().await {
+                        Ok(_) => {
+                            tracing::info!(
+                                "Successfully killed vi5 process with PID: {}",
+                                process.id().unwrap_or(0)
+                            );
+                        }
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to kill vi5 process with PID: {}: {}",
+                                process.id().unwrap_or(0),
+                                e
+                            );
+                        }
+                    }
                 }
-            }
-        }
+                self.render_loop.close().await;
+                tracing::info!("Graceful shutdown completed: {:?}", report);
+                report
+            })
+            .await
     }
 }
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShutdownReport {
+    pub drained_renders: usize,
+    pub cancelled_renders: usize,
+    pub timed_out: bool,
+}
+
+/// 処理中のレンダリングを数えて、シャットダウン時に待機・キャンセルできるようにする。
+pub struct RenderTracker {
+    accepting: AtomicBool,
+    in_flight: AtomicUsize,
+    finished: tokio::sync::Notify,
+    cancel_tx: tokio::sync::watch::Sender<bool>,
+}
+
+pub struct RenderTicket {
+    tracker: Arc<RenderTracker>,
+    cancel_rx: tokio::sync::watch::Receiver<bool>,
+}
+
+impl Drop for RenderTicket {
+    fn drop(&mut self) {
+        self.tracker.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.tracker.finished.notify_waiters();
+    }
+}
+
+impl RenderTicket {
+    /// シャットダウンの期限を過ぎて、レンダリングを打ち切るよう求められているか。
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_rx.borrow()
+    }
+}
+
+impl RenderTracker {
+    pub fn new() -> Self {
+        let (cancel_tx, _) = tokio::sync::watch::channel(false);
+        Self {
+            accepting: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+            finished: tokio::sync::Notify::new(),
+            cancel_tx,
+        }
+    }
+
+    pub fn begin(self: &Arc<Self>) -> Option<RenderTicket> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let ticket = RenderTicket {
+            tracker: self.clone(),
+            cancel_rx: self.cancel_tx.subscribe(),
+        };
+        // 先にカウントしてから確認しないと、drainの開始と競合したときに取りこぼす
+        if !self.accepting.load(Ordering::SeqCst) {
+            return None;
+        }
+        Some(ticket)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// 新規のレンダリングを拒否し、処理中のものが終わるのを`deadline`まで待つ。
+    /// 間に合わなかったものはキャンセルする。
+    pub async fn drain(&self, deadline: Duration) -> ShutdownReport {
+        self.accepting.store(false, Ordering::SeqCst);
+        let initial = self.in_flight();
+        tracing::info!(
+            "Draining {} in-flight render(s) (deadline: {:?})",
+            initial,
+            deadline
+        );
+        let drained = tokio::time::timeout(deadline, self.wait_until_idle()).await;
+        let remaining = self.in_flight();
+        let timed_out = drained.is_err();
+        if timed_out {
+            tracing::warn!(
+                "Shutdown deadline exceeded, cancelling {} render(s)",
+                remaining
+            );
+            let _ = self.cancel_tx.send(true);
+            // キャンセルされたレンダリングがエラーを返し終わるのを少しだけ待つ
+            let _ = tokio::time::timeout(Duration::from_secs(1), self.wait_until_idle()).await;
+        }
+        ShutdownReport {
+            drained_renders: initial.saturating_sub(remaining),
+            cancelled_renders: remaining,
+            timed_out,
+        }
+    }
+
+    async fn wait_until_idle(&self) {
+        loop {
+            let finished = self.finished.notified();
+            if self.in_flight() == 0 {
+                return;
+            }
+            finished.await;
+        }
+    }
+}
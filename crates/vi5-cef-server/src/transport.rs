@@ -0,0 +1,165 @@
+#[cfg(windows)]
+use std::pin::Pin;
+#[cfg(windows)]
+use std::task::{Context, Poll};
+
+use futures::Stream;
+
+#[derive(Debug, Clone)]
+pub enum ListenAddress {
+    Tcp(u16),
+    /// Unixではソケットファイルのパス、Windowsでは名前付きパイプの名前（`\\.\pipe\...`）。
+    Local(String),
+}
+
+impl std::fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddress::Tcp(port) => write!(f, "[::1]:{}", port),
+            ListenAddress::Local(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// `authorization: Bearer <token>`を検証するインターセプター。
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    token: Option<String>,
+}
+
+impl AuthInterceptor {
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+}
+
+impl tonic::service::Interceptor for AuthInterceptor {
+    fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let Some(expected) = &self.token else {
+            return Ok(request);
+        };
+        let provided = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match provided {
+            Some(provided) if constant_time_eq(provided.as_bytes(), expected.as_bytes()) => {
+                Ok(request)
+            }
+            _ => Err(tonic::Status::unauthenticated("Invalid or missing token")),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(unix)]
+pub fn local_incoming(
+    path: &str,
+) -> anyhow::Result<impl Stream<Item = std::io::Result<tokio::net::UnixStream>>> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // 前回のプロセスが残したソケットファイルがあるとbindに失敗する
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path);
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("Another server is already listening on {}", path);
+        }
+        tracing::info!("Removing stale socket file {}", path);
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    Ok(tokio_stream::wrappers::UnixListenerStream::new(listener))
+}
+
+/// `local_incoming`で作ったソケットファイルを消す。
+#[cfg(unix)]
+pub fn remove_local_socket(path: &str) {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!("Failed to remove socket file {}: {}", path, e),
+    }
+}
+
+/// 名前付きパイプはハンドルを閉じれば消えるので、何もしない。
+#[cfg(windows)]
+pub fn remove_local_socket(_name: &str) {}
+
+#[cfg(windows)]
+pub fn local_incoming(
+    name: &str,
+) -> anyhow::Result<impl Stream<Item = std::io::Result<NamedPipeConnection>>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let first = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(name)?;
+    let name = name.to_string();
+    Ok(futures::stream::unfold(Some(first), move |server| {
+        let name = name.clone();
+        async move {
+            let server = server?;
+            if let Err(e) = server.connect().await {
+                let next = ServerOptions::new().create(&name).ok();
+                return Some((Err(e), next));
+            }
+            // 次の接続を待つインスタンスを先に作っておく
+            match ServerOptions::new().create(&name) {
+                Ok(next) => Some((Ok(NamedPipeConnection(server)), Some(next))),
+                Err(e) => {
+                    tracing::error!("Failed to create next named pipe instance: {}", e);
+                    Some((Ok(NamedPipeConnection(server)), None))
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(windows)]
+pub struct NamedPipeConnection(tokio::net::windows::named_pipe::NamedPipeServer);
+
+#[cfg(windows)]
+impl tonic::transport::server::Connected for NamedPipeConnection {
+    type ConnectInfo = ();
+
+    fn connect_info(&self) -> Self::ConnectInfo {}
+}
+
+#[cfg(windows)]
+impl tokio::io::AsyncRead for NamedPipeConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+#[cfg(windows)]
+impl tokio::io::AsyncWrite for NamedPipeConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
@@ -1,32 +1,101 @@
 use crate::convert::ConversionError;
 use crate::protocol;
-use crate::types::{InitializeResponse, Notification, RenderRequest, RenderResponse};
+use crate::types::{
+    HeartbeatResponse, InitializeResponse, Notification, RenderRequest, RenderResponse,
+    ShutdownResponse,
+};
 use tonic::IntoRequest;
 
-type LibServerClient =
-    protocol::libserver::lib_server_client::LibServerClient<tonic::transport::Channel>;
+type LibServerClient = protocol::libserver::lib_server_client::LibServerClient<
+    tonic::service::interceptor::InterceptedService<tonic::transport::Channel, AuthInterceptor>,
+>;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     inner: LibServerClient,
+    channel: tonic::transport::Channel,
     next_nonce: i32,
 }
 
+#[derive(Clone, Default)]
+struct AuthInterceptor {
+    authorization: Option<tonic::metadata::AsciiMetadataValue>,
+}
+
+impl std::fmt::Debug for AuthInterceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthInterceptor")
+            .field("has_token", &self.authorization.is_some())
+            .finish()
+    }
+}
+
+impl tonic::service::Interceptor for AuthInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
+
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
 impl Client {
+    fn from_channel(channel: tonic::transport::Channel, interceptor: AuthInterceptor) -> Self {
+        let inner = LibServerClient::with_interceptor(channel.clone(), interceptor)
+            .max_decoding_message_size(usize::MAX);
+        Self {
+            inner,
+            channel,
+            next_nonce: 1,
+        }
+    }
+
     pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
     where
         D: TryInto<tonic::transport::Endpoint>,
         D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
     {
-        let inner = LibServerClient::connect(
-            tonic::transport::Endpoint::new(dst)?
-                .connect_timeout(std::time::Duration::from_secs(60)),
-        )
-        .await?
-        .max_decoding_message_size(usize::MAX);
+        let channel = tonic::transport::Endpoint::new(dst)?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await?;
+        Ok(Self::from_channel(channel, AuthInterceptor::default()))
+    }
+
+    /// Unixドメインソケット（Windowsでは名前付きパイプ）で接続する。
+    pub async fn connect_local(path: impl Into<String>) -> Result<Self, tonic::transport::Error> {
+        let path = path.into();
+        // URIはコネクタでは使われないが、Endpointの作成には必要
+        let channel = tonic::transport::Endpoint::from_static("http://[::1]:50051")
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect_with_connector(tower::service_fn(move |_: tonic::transport::Uri| {
+                let path = path.clone();
+                async move {
+                    let stream = connect_local_stream(&path).await?;
+                    Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+                }
+            }))
+            .await?;
+        Ok(Self::from_channel(channel, AuthInterceptor::default()))
+    }
+
+    pub fn with_token(
+        self,
+        token: &str,
+    ) -> Result<Self, tonic::metadata::errors::InvalidMetadataValue> {
+        let interceptor = AuthInterceptor {
+            authorization: Some(format!("Bearer {}", token).parse()?),
+        };
         Ok(Self {
-            inner,
-            next_nonce: 1,
+            next_nonce: self.next_nonce,
+            ..Self::from_channel(self.channel, interceptor)
         })
     }
 
@@ -82,9 +151,40 @@ impl Client {
         Ok(())
     }
 
-    pub async fn shutdown(&mut self) -> Result<(), tonic::Status> {
-        self.inner.shutdown(protocol::common::Void {}).await?;
-        Ok(())
+    pub async fn shutdown(
+        &mut self,
+        deadline: Option<std::time::Duration>,
+    ) -> Result<ShutdownResponse, tonic::Status> {
+        let request = protocol::libserver::ShutdownRequest {
+            deadline_ms: deadline.map_or(0, |deadline| deadline.as_millis() as u64),
+        };
+        let response = self.inner.shutdown(request).await?.into_inner();
+        ShutdownResponse::try_from(response).map_err(ConversionError::into_status)
+    }
+
+    pub async fn heartbeat(&mut self) -> Result<HeartbeatResponse, tonic::Status> {
+        let response = self
+            .inner
+            .heartbeat(protocol::common::Void {})
+            .await?
+            .into_inner();
+        HeartbeatResponse::try_from(response).map_err(ConversionError::into_status)
+    }
+
+    /// 返り値の[`HeartbeatHandle`]がdropされるまで、`interval`ごとにハートビートを送り続ける。
+    pub fn spawn_heartbeat(&self, interval: std::time::Duration) -> HeartbeatHandle {
+        let mut client = self.clone();
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = client.heartbeat().await {
+                    tracing::warn!("Failed to send heartbeat: {}", e);
+                }
+            }
+        });
+        HeartbeatHandle { task }
     }
 
     pub async fn subscribe_notifications(&mut self) -> Result<NotificationStream, tonic::Status> {
@@ -97,6 +197,17 @@ impl Client {
     }
 }
 
+#[derive(Debug)]
+pub struct HeartbeatHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for HeartbeatHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 pub struct NotificationStream {
     inner: tonic::Streaming<protocol::libserver::Notification>,
 }
@@ -111,3 +222,38 @@ impl NotificationStream {
         }
     }
 }
+
+// サーバーの起動直後はまだソケットが作られていないことがあるので、接続できるまで待つ。
+// 全体のタイムアウトはEndpointのconnect_timeoutに任せる。
+#[cfg(unix)]
+async fn connect_local_stream(path: &str) -> std::io::Result<tokio::net::UnixStream> {
+    loop {
+        match tokio::net::UnixStream::connect(path).await {
+            Ok(stream) => return Ok(stream),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+                ) => {}
+            Err(e) => return Err(e),
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[cfg(windows)]
+async fn connect_local_stream(
+    name: &str,
+) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    const ERROR_PIPE_BUSY: i32 = 231;
+    loop {
+        match tokio::net::windows::named_pipe::ClientOptions::new().open(name) {
+            Ok(client) => return Ok(client),
+            Err(e)
+                if e.kind() == std::io::ErrorKind::NotFound
+                    || e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {}
+            Err(e) => return Err(e),
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
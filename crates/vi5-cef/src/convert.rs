@@ -1,11 +1,12 @@
 use crate::protocol;
 use crate::types::{
-    Color, FrameInfo, InitializeResponse, LogNotification, LogNotificationLevel, Notification,
-    ObjectInfo, ObjectInfosNotification, Parameter, ParameterDefinition, ParameterType,
-    ParameterValue, RenderRequest, RenderResponse, RenderResponseData,
+    AlphaMode, Color, ColorSpace, FileValue, FrameInfo, HeartbeatResponse, InitializeResponse,
+    InputImage, LogNotification, LogNotificationLevel, Notification, ObjectDependencies,
+    ObjectInfo, ObjectInfosNotification, ObjectKind, Parameter, ParameterDefinition, ParameterType,
+    ParameterValue, RenderRequest, RenderResponse, RenderResponseData, ShutdownResponse,
 };
 
-use crate::types::NumberStep;
+use crate::types::{NumberComponent, NumberStep};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConversionError {
@@ -17,10 +18,18 @@ pub enum ConversionError {
     MissingParameterType,
     #[error("missing parameter type kind")]
     MissingParameterTypeKind,
+    #[error("missing number component")]
+    MissingNumberComponent,
     #[error("invalid number step: {0}")]
     InvalidNumberStep(i32),
     #[error("invalid notification level: {0}")]
     InvalidNotificationLevel(i32),
+    #[error("invalid object kind: {0}")]
+    InvalidObjectKind(i32),
+    #[error("invalid alpha mode: {0}")]
+    InvalidAlphaMode(i32),
+    #[error("invalid color space: {0}")]
+    InvalidColorSpace(i32),
 }
 
 impl ConversionError {
@@ -42,6 +51,18 @@ impl RenderRequest {
                 .map(Parameter::into_proto)
                 .collect(),
             is_offline: self.is_offline,
+            seed: self.seed,
+            input_image: self.input_image.map(InputImage::into_proto),
+        }
+    }
+}
+
+impl InputImage {
+    fn into_proto(self) -> protocol::common::InputImage {
+        protocol::common::InputImage {
+            width: self.width as _,
+            height: self.height as _,
+            image_data: self.image_data,
         }
     }
 }
@@ -84,6 +105,30 @@ impl ParameterValue {
             Self::Color(value) => {
                 protocol::common::parameter::Value::ColorValue(value.into_proto())
             }
+            Self::Select(value) => protocol::common::parameter::Value::SelectValue(value),
+            Self::File(value) => {
+                protocol::common::parameter::Value::FileValue(protocol::common::FileValue {
+                    path: value.path,
+                    modified_ms: value.modified_ms,
+                    data: vec![],
+                })
+            }
+            Self::Vector2 { x, y } => {
+                protocol::common::parameter::Value::Vector2Value(protocol::common::Vector2 { x, y })
+            }
+            Self::Vector3 { x, y, z } => {
+                protocol::common::parameter::Value::Vector3Value(protocol::common::Vector3 {
+                    x,
+                    y,
+                    z,
+                })
+            }
+            Self::Range { start, end } => {
+                protocol::common::parameter::Value::RangeValue(protocol::common::Range {
+                    start,
+                    end,
+                })
+            }
         }
     }
 }
@@ -110,6 +155,29 @@ impl TryFrom<protocol::libserver::InitializeResponse> for InitializeResponse {
     }
 }
 
+impl TryFrom<protocol::libserver::HeartbeatResponse> for HeartbeatResponse {
+    type Error = ConversionError;
+
+    fn try_from(value: protocol::libserver::HeartbeatResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            lease_grace_period: (value.lease_grace_period_ms > 0)
+                .then(|| std::time::Duration::from_millis(value.lease_grace_period_ms)),
+        })
+    }
+}
+
+impl TryFrom<protocol::libserver::ShutdownResponse> for ShutdownResponse {
+    type Error = ConversionError;
+
+    fn try_from(value: protocol::libserver::ShutdownResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            drained_renders: value.drained_renders as _,
+            cancelled_renders: value.cancelled_renders as _,
+            timed_out: value.timed_out,
+        })
+    }
+}
+
 impl TryFrom<protocol::common::ObjectInfo> for ObjectInfo {
     type Error = ConversionError;
 
@@ -123,10 +191,29 @@ impl TryFrom<protocol::common::ObjectInfo> for ObjectInfo {
             id: value.id,
             label: value.label,
             parameter_definitions,
+            stateful: value.stateful,
+            version: value.version,
+            dependencies: value.dependencies.map(ObjectDependencies::from),
+            kind: match value.kind {
+                0 => ObjectKind::Generator,
+                1 => ObjectKind::Filter,
+                kind => return Err(ConversionError::InvalidObjectKind(kind)),
+            },
         })
     }
 }
 
+impl From<protocol::common::ObjectDependencies> for ObjectDependencies {
+    fn from(value: protocol::common::ObjectDependencies) -> Self {
+        Self {
+            time: value.time,
+            position: value.position,
+            screen_size: value.screen_size,
+            parameters: value.parameters,
+        }
+    }
+}
+
 impl TryFrom<protocol::common::ParameterDefinition> for ParameterDefinition {
     type Error = ConversionError;
 
@@ -142,6 +229,9 @@ impl TryFrom<protocol::common::ParameterDefinition> for ParameterDefinition {
             parameter_type,
             label: value.label,
             default_value,
+            group: Some(value.group).filter(|group| !group.is_empty()),
+            description: Some(value.description).filter(|description| !description.is_empty()),
+            order: value.order,
         })
     }
 }
@@ -158,26 +248,64 @@ impl TryFrom<protocol::common::ParameterType> for ParameterType {
             protocol::common::parameter_type::Kind::Text(_) => Ok(Self::Text),
             protocol::common::parameter_type::Kind::Boolean(_) => Ok(Self::Boolean),
             protocol::common::parameter_type::Kind::Number(number) => {
-                let step = match number.step {
-                    0 => NumberStep::One,
-                    1 => NumberStep::PointOne,
-                    2 => NumberStep::PointZeroOne,
-                    3 => NumberStep::PointZeroZeroOne,
-                    _ => {
-                        return Err(ConversionError::InvalidNumberStep(number.step));
-                    }
-                };
-                Ok(Self::Number {
-                    step,
-                    min: number.min,
-                    max: number.max,
-                })
+                let NumberComponent { step, min, max } = NumberComponent::try_from(number)?;
+                Ok(Self::Number { step, min, max })
             }
             protocol::common::parameter_type::Kind::Color(_) => Ok(Self::Color),
+            protocol::common::parameter_type::Kind::Select(select) => Ok(Self::Select {
+                options: select
+                    .options
+                    .into_iter()
+                    .map(|option| (option.label, option.value))
+                    .collect(),
+            }),
+            protocol::common::parameter_type::Kind::File(file) => Ok(Self::File {
+                filters: file.filters,
+            }),
+            protocol::common::parameter_type::Kind::Vector2(vector) => Ok(Self::Vector2 {
+                x: number_component(vector.x)?,
+                y: number_component(vector.y)?,
+            }),
+            protocol::common::parameter_type::Kind::Vector3(vector) => Ok(Self::Vector3 {
+                x: number_component(vector.x)?,
+                y: number_component(vector.y)?,
+                z: number_component(vector.z)?,
+            }),
+            protocol::common::parameter_type::Kind::Range(range) => Ok(Self::Range {
+                start: number_component(range.start)?,
+                end: number_component(range.end)?,
+            }),
         }
     }
 }
 
+fn number_component(
+    value: Option<protocol::common::ParameterNumber>,
+) -> Result<NumberComponent, ConversionError> {
+    NumberComponent::try_from(value.ok_or(ConversionError::MissingNumberComponent)?)
+}
+
+impl TryFrom<protocol::common::ParameterNumber> for NumberComponent {
+    type Error = ConversionError;
+
+    fn try_from(value: protocol::common::ParameterNumber) -> Result<Self, Self::Error> {
+        let step = match value.step {
+            0 => NumberStep::One,
+            1 => NumberStep::PointOne,
+            2 => NumberStep::PointZeroOne,
+            3 => NumberStep::PointZeroZeroOne,
+            _ => {
+                return Err(ConversionError::InvalidNumberStep(value.step));
+            }
+        };
+        Ok(Self {
+            step,
+            min: value.min,
+            max: value.max,
+        })
+    }
+}
+
 impl TryFrom<protocol::common::Parameter> for Parameter {
     type Error = ConversionError;
 
@@ -203,6 +331,24 @@ impl TryFrom<protocol::common::parameter::Value> for ParameterValue {
             protocol::common::parameter::Value::ColorValue(value) => {
                 Self::Color(Color::from(value))
             }
+            protocol::common::parameter::Value::SelectValue(value) => Self::Select(value),
+            protocol::common::parameter::Value::FileValue(value) => Self::File(FileValue {
+                path: value.path,
+                modified_ms: value.modified_ms,
+            }),
+            protocol::common::parameter::Value::Vector2Value(value) => Self::Vector2 {
+                x: value.x,
+                y: value.y,
+            },
+            protocol::common::parameter::Value::Vector3Value(value) => Self::Vector3 {
+                x: value.x,
+                y: value.y,
+                z: value.z,
+            },
+            protocol::common::parameter::Value::RangeValue(value) => Self::Range {
+                start: value.start,
+                end: value.end,
+            },
         })
     }
 }
@@ -231,6 +377,16 @@ impl TryFrom<protocol::libserver::RenderResponse> for RenderResponse {
                     width: success.width,
                     height: success.height,
                     image_data: success.image_data,
+                    alpha_mode: match success.alpha_mode {
+                        0 => AlphaMode::Straight,
+                        1 => AlphaMode::Premultiplied,
+                        mode => return Err(ConversionError::InvalidAlphaMode(mode)),
+                    },
+                    color_space: match success.color_space {
+                        0 => ColorSpace::Srgb,
+                        1 => ColorSpace::LinearSrgb,
+                        space => return Err(ConversionError::InvalidColorSpace(space)),
+                    },
                 }
             }
             protocol::libserver::render_response::Response::ErrorMessage(message) => {
@@ -1,11 +1,14 @@
 mod client;
 mod convert;
+mod pixel;
 mod protocol;
 mod types;
 
-pub use client::{Client, NotificationStream};
+pub use client::{Client, HeartbeatHandle, NotificationStream};
+pub use pixel::to_straight_srgb;
 pub use types::{
-    Color, FrameInfo, InitializeResponse, LogNotificationLevel, Notification, ObjectInfo,
-    Parameter, ParameterDefinition, ParameterType, ParameterValue, RenderRequest, RenderResponse,
-    RenderResponseData,
+    AlphaMode, Color, ColorSpace, FileValue, FrameInfo, HeartbeatResponse, InitializeResponse,
+    InputImage, LogNotificationLevel, Notification, NumberComponent, NumberStep,
+    ObjectDependencies, ObjectInfo, ObjectKind, Parameter, ParameterDefinition, ParameterType,
+    ParameterValue, RenderRequest, RenderResponse, RenderResponseData, ShutdownResponse,
 };
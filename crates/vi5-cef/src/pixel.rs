@@ -0,0 +1,143 @@
+use crate::types::{AlphaMode, ColorSpace};
+
+/// RGBAの画像をアルファ非乗算のsRGBに変換する。
+/// AviUtl2の`putpixeldata`やPNGはこの形式を前提にしている。
+pub fn to_straight_srgb(image_data: &mut [u8], alpha_mode: AlphaMode, color_space: ColorSpace) {
+    if alpha_mode == AlphaMode::Straight && color_space == ColorSpace::Srgb {
+        return;
+    }
+    let encode = match color_space {
+        ColorSpace::Srgb => None,
+        ColorSpace::LinearSrgb => Some(linear_to_srgb_table()),
+    };
+    for pixel in image_data.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            let mut value = *channel;
+            // 乗算を戻すのは線形のまま行う
+            if alpha_mode == AlphaMode::Premultiplied {
+                value = match alpha {
+                    0 => 0,
+                    255 => value,
+                    alpha => ((value as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+            }
+            if let Some(table) = &encode {
+                value = table[value as usize];
+            }
+            *channel = value;
+        }
+    }
+}
+
+fn linear_to_srgb_table() -> [u8; 256] {
+    std::array::from_fn(|i| {
+        let linear = i as f64 / 255.0;
+        let srgb = if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        (srgb * 255.0).round() as u8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(pixel: [u8; 4], alpha_mode: AlphaMode, color_space: ColorSpace) -> [u8; 4] {
+        let mut image_data = pixel;
+        to_straight_srgb(&mut image_data, alpha_mode, color_space);
+        image_data
+    }
+
+    #[test]
+    fn keeps_straight_srgb_as_is() {
+        let pixel = [10, 200, 30, 40];
+        assert_eq!(convert(pixel, AlphaMode::Straight, ColorSpace::Srgb), pixel);
+    }
+
+    #[test]
+    fn clears_color_of_transparent_pixels() {
+        assert_eq!(
+            convert([10, 20, 30, 0], AlphaMode::Premultiplied, ColorSpace::Srgb),
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn keeps_opaque_pixels() {
+        assert_eq!(
+            convert(
+                [10, 20, 30, 255],
+                AlphaMode::Premultiplied,
+                ColorSpace::Srgb
+            ),
+            [10, 20, 30, 255]
+        );
+    }
+
+    #[test]
+    fn unpremultiplies_with_rounding() {
+        // 64 * 255 / 128 = 127.5、1 * 255 / 2 = 127.5、50 * 255 / 100 = 127.5はどれも四捨五入される
+        assert_eq!(
+            convert(
+                [64, 0, 128, 128],
+                AlphaMode::Premultiplied,
+                ColorSpace::Srgb
+            ),
+            [128, 0, 255, 128]
+        );
+        assert_eq!(
+            convert([1, 1, 0, 2], AlphaMode::Premultiplied, ColorSpace::Srgb),
+            [128, 128, 0, 2]
+        );
+        assert_eq!(
+            convert([50, 1, 2, 100], AlphaMode::Premultiplied, ColorSpace::Srgb),
+            [128, 3, 5, 100]
+        );
+    }
+
+    #[test]
+    fn clamps_channels_larger_than_alpha() {
+        // 乗算済みとしては不正な値でも、255を超えないようにする
+        assert_eq!(
+            convert(
+                [200, 255, 101, 100],
+                AlphaMode::Premultiplied,
+                ColorSpace::Srgb
+            ),
+            [255, 255, 255, 100]
+        );
+    }
+
+    #[test]
+    fn encodes_linear_to_srgb() {
+        assert_eq!(
+            convert([0, 1, 128, 77], AlphaMode::Straight, ColorSpace::LinearSrgb),
+            [0, 13, 188, 77]
+        );
+        assert_eq!(
+            convert(
+                [255, 255, 255, 255],
+                AlphaMode::Straight,
+                ColorSpace::LinearSrgb
+            ),
+            [255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn unpremultiplies_before_encoding() {
+        // 乗算を戻すと線形で128になり、それをsRGBにすると188になる
+        assert_eq!(
+            convert(
+                [64, 0, 0, 128],
+                AlphaMode::Premultiplied,
+                ColorSpace::LinearSrgb
+            ),
+            [188, 0, 0, 128]
+        );
+    }
+}
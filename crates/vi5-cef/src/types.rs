@@ -4,11 +4,41 @@ pub struct InitializeResponse {
     pub renderer_version: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct HeartbeatResponse {
+    pub lease_grace_period: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShutdownResponse {
+    pub drained_renders: usize,
+    pub cancelled_renders: usize,
+    pub timed_out: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct ObjectInfo {
     pub id: String,
     pub label: String,
     pub parameter_definitions: Vec<ParameterDefinition>,
+    pub stateful: bool,
+    pub version: String,
+    pub dependencies: Option<ObjectDependencies>,
+    pub kind: ObjectKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Generator,
+    Filter,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectDependencies {
+    pub time: bool,
+    pub position: bool,
+    pub screen_size: bool,
+    pub parameters: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +47,9 @@ pub struct ParameterDefinition {
     pub parameter_type: ParameterType,
     pub label: String,
     pub default_value: Option<Parameter>,
+    pub group: Option<String>,
+    pub description: Option<String>,
+    pub order: Option<i32>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -57,7 +90,7 @@ impl TryFrom<f64> for NumberStep {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParameterType {
     String,
     Text,
@@ -68,6 +101,35 @@ pub enum ParameterType {
         max: f64,
     },
     Color,
+    // (ラベル, 値)
+    Select {
+        options: Vec<(String, String)>,
+    },
+    // "*.png"などのパターン
+    File {
+        filters: Vec<String>,
+    },
+    Vector2 {
+        x: NumberComponent,
+        y: NumberComponent,
+    },
+    Vector3 {
+        x: NumberComponent,
+        y: NumberComponent,
+        z: NumberComponent,
+    },
+    Range {
+        start: NumberComponent,
+        end: NumberComponent,
+    },
+}
+
+// Vector2などの成分ごとの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberComponent {
+    pub step: NumberStep,
+    pub min: f64,
+    pub max: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -77,6 +139,15 @@ pub struct RenderRequest {
     pub frame_info: FrameInfo,
     pub parameters: Vec<Parameter>,
     pub is_offline: bool,
+    pub seed: u32,
+    pub input_image: Option<InputImage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InputImage {
+    pub width: usize,
+    pub height: usize,
+    pub image_data: Vec<u8>,
 }
 
 #[derive(Debug, Clone)]
@@ -108,6 +179,31 @@ pub enum ParameterValue {
     Number(f64),
     Bool(bool),
     Color(Color),
+    Select(String),
+    File(FileValue),
+    Vector2 { x: f64, y: f64 },
+    Vector3 { x: f64, y: f64, z: f64 },
+    Range { start: f64, end: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileValue {
+    pub path: String,
+    // ファイルが存在しなければ0
+    pub modified_ms: i64,
+}
+
+impl FileValue {
+    /// ファイルの更新日時を読んで作る。
+    pub fn from_path(path: impl Into<String>) -> Self {
+        let path = path.into();
+        let modified_ms = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_millis() as i64);
+        Self { path, modified_ms }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -130,10 +226,25 @@ pub enum RenderResponseData {
         width: i32,
         height: i32,
         image_data: Vec<u8>,
+        alpha_mode: AlphaMode,
+        color_space: ColorSpace,
     },
     Error(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    Straight,
+    // 色の成分にアルファが掛かっている
+    Premultiplied,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    LinearSrgb,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogNotificationLevel {
     Info,
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use crate::manifest::frame_file_name;
+use crate::render::RenderSettings;
+use crate::server::Server;
+
+/// ゴールデンイメージテストの仕様。パスは仕様ファイルのディレクトリからの相対パス。
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    pub project: std::path::PathBuf,
+    #[serde(default = "default_goldens")]
+    pub goldens: std::path::PathBuf,
+    /// 各チャンネルの差の許容値（0-255）
+    #[serde(default)]
+    pub tolerance: u8,
+    /// 許容値を超えてもよいピクセル数
+    #[serde(default)]
+    pub max_mismatched_pixels: usize,
+    #[serde(default = "default_screen_width")]
+    pub screen_width: usize,
+    #[serde(default = "default_screen_height")]
+    pub screen_height: usize,
+    #[serde(default = "default_framerate")]
+    pub framerate: f64,
+    pub cases: Vec<Case>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Case {
+    pub name: String,
+    pub object: String,
+    #[serde(default)]
+    pub params: HashMap<String, serde_json::Value>,
+    pub frames: Vec<usize>,
+    pub total_frames: Option<usize>,
+    #[serde(default)]
+    pub object_id: i64,
+    #[serde(default)]
+    pub seed: u32,
+    pub tolerance: Option<u8>,
+    pub max_mismatched_pixels: Option<usize>,
+}
+
+fn default_goldens() -> std::path::PathBuf {
+    "goldens".into()
+}
+
+fn default_screen_width() -> usize {
+    1920
+}
+
+fn default_screen_height() -> usize {
+    1080
+}
+
+fn default_framerate() -> f64 {
+    60.0
+}
+
+impl Spec {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let mut spec: Spec = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or(std::path::Path::new("."));
+        spec.project = base_dir.join(&spec.project);
+        spec.goldens = base_dir.join(&spec.goldens);
+        crate::render::check_framerate(spec.framerate)
+            .map_err(|e| anyhow::anyhow!("Invalid spec {}: {}", path.display(), e))?;
+        for case in &spec.cases {
+            // ケース名はそのままディレクトリ名になる
+            if case.name.is_empty()
+                || !case
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                || case.name.starts_with('.')
+            {
+                anyhow::bail!("Invalid case name (use [A-Za-z0-9._-]): {:?}", case.name);
+            }
+        }
+        Ok(spec)
+    }
+}
+
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub max_difference: u8,
+    pub diff_image: image::RgbaImage,
+}
+
+/// 2つの画像をピクセルごとに比較する。
+/// 差分画像では許容値を超えたピクセルを赤、それ以外を期待画像の薄いグレースケールで表す。
+/// サイズは同じである必要がある。
+pub fn compare(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    tolerance: u8,
+) -> Comparison {
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let diff_image = image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let expected = expected.get_pixel(x, y);
+        let actual = actual.get_pixel(x, y);
+        let difference = expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, a] = expected.0;
+            let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+            let luma = (luma * a as u32 / 255 / 4 + 191) as u8;
+            image::Rgba([luma, luma, luma, 255])
+        }
+    });
+    Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff_image,
+    }
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Passed,
+    Updated,
+    Failed(String),
+}
+
+/// 仕様のすべてのケースをレンダリングしてゴールデンイメージと比較する。
+/// `update_goldens`が真の場合は比較せずにゴールデンイメージを書き換える。
+pub async fn run(
+    server: &mut Server,
+    spec: &Spec,
+    diff_dir: &std::path::Path,
+    update_goldens: bool,
+    batch_size: usize,
+) -> anyhow::Result<bool> {
+    let project = server.initialize(&spec.project).await?;
+
+    let mut failed_cases = 0;
+    for case in &spec.cases {
+        let object_info = project
+            .object_infos
+            .iter()
+            .find(|info| info.id == case.object)
+            .ok_or_else(|| anyhow::anyhow!("[{}] Object not found: {}", case.name, case.object))?;
+        let parameters = crate::params::build_parameters(object_info, &case.params)
+            .map_err(|e| anyhow::anyhow!("[{}] {}", case.name, e))?;
+        let settings = RenderSettings {
+            screen_width: spec.screen_width,
+            screen_height: spec.screen_height,
+            framerate: spec.framerate,
+            total_frames: case
+                .total_frames
+                .unwrap_or_else(|| case.frames.iter().max().map_or(1, |max| max + 1)),
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            object_id: case.object_id,
+            is_offline: true,
+            seed: case.seed,
+            batch_size,
+        };
+        let rendered = crate::render::render_frames(
+            &mut server.client,
+            &case.object,
+            &parameters,
+            &case.frames,
+            &settings,
+        )
+        .await?;
+
+        let golden_dir = spec.goldens.join(&case.name);
+        let case_diff_dir = diff_dir.join(&case.name);
+        // 前回の実行で書き出された差分が残っていると紛らわしい
+        if case_diff_dir.exists() {
+            std::fs::remove_dir_all(&case_diff_dir)?;
+        }
+        let mut case_failed = false;
+        for frame in rendered {
+            let outcome = check_frame(
+                &frame,
+                &golden_dir,
+                &case_diff_dir,
+                case.tolerance.unwrap_or(spec.tolerance),
+                case.max_mismatched_pixels
+                    .unwrap_or(spec.max_mismatched_pixels),
+                update_goldens,
+            )?;
+            match outcome {
+                Outcome::Passed => {
+                    tracing::info!("[{}] frame {}: ok", case.name, frame.frame);
+                }
+                Outcome::Updated => {
+                    tracing::info!("[{}] frame {}: golden updated", case.name, frame.frame);
+                }
+                Outcome::Failed(reason) => {
+                    tracing::error!("[{}] frame {}: {}", case.name, frame.frame, reason);
+                    case_failed = true;
+                }
+            }
+        }
+        if case_failed {
+            failed_cases += 1;
+        }
+    }
+
+    if failed_cases > 0 {
+        tracing::error!(
+            "{} of {} case(s) failed, diffs are written to {}",
+            failed_cases,
+            spec.cases.len(),
+            diff_dir.display()
+        );
+    } else {
+        tracing::info!("All {} case(s) passed", spec.cases.len());
+    }
+    Ok(failed_cases == 0)
+}
+
+fn check_frame(
+    frame: &crate::render::RenderedFrame,
+    golden_dir: &std::path::Path,
+    diff_dir: &std::path::Path,
+    tolerance: u8,
+    max_mismatched_pixels: usize,
+    update_goldens: bool,
+) -> anyhow::Result<Outcome> {
+    let actual = match &frame.result {
+        Ok(image) => image,
+        Err(message) => return Ok(Outcome::Failed(format!("render failed: {}", message))),
+    };
+    let file_name = frame_file_name(frame.frame);
+    let golden_path = golden_dir.join(&file_name);
+
+    if update_goldens {
+        std::fs::create_dir_all(golden_dir)?;
+        actual.save_with_format(&golden_path, image::ImageFormat::Png)?;
+        return Ok(Outcome::Updated);
+    }
+
+    let write_actual = || -> anyhow::Result<()> {
+        std::fs::create_dir_all(diff_dir)?;
+        actual.save_with_format(
+            diff_dir.join(file_name.replace(".png", ".actual.png")),
+            image::ImageFormat::Png,
+        )?;
+        Ok(())
+    };
+
+    if !golden_path.exists() {
+        write_actual()?;
+        return Ok(Outcome::Failed(format!(
+            "golden image {} does not exist (run with --update-goldens to create it)",
+            golden_path.display()
+        )));
+    }
+    let expected = image::open(&golden_path)?.into_rgba8();
+    let Err(mismatch) = evaluate(&expected, actual, tolerance, max_mismatched_pixels) else {
+        return Ok(Outcome::Passed);
+    };
+    write_actual()?;
+    if let Some(diff_image) = &mismatch.diff_image {
+        diff_image.save_with_format(
+            diff_dir.join(file_name.replace(".png", ".diff.png")),
+            image::ImageFormat::Png,
+        )?;
+    }
+    Ok(Outcome::Failed(mismatch.reason))
+}
+
+struct Mismatch {
+    reason: String,
+    // サイズが違うときは作らない
+    diff_image: Option<image::RgbaImage>,
+}
+
+/// 期待画像と比べて、許容値を超えたピクセルが`max_mismatched_pixels`以下なら合格にする。
+fn evaluate(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    tolerance: u8,
+    max_mismatched_pixels: usize,
+) -> Result<(), Mismatch> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(Mismatch {
+            reason: format!(
+                "size mismatch: expected {}x{}, got {}x{}",
+                expected.width(),
+                expected.height(),
+                actual.width(),
+                actual.height()
+            ),
+            diff_image: None,
+        });
+    }
+
+    let comparison = compare(expected, actual, tolerance);
+    if comparison.mismatched_pixels <= max_mismatched_pixels {
+        return Ok(());
+    }
+    Err(Mismatch {
+        reason: format!(
+            "{} pixel(s) differ by more than {} (max difference: {})",
+            comparison.mismatched_pixels, tolerance, comparison.max_difference
+        ),
+        diff_image: Some(comparison.diff_image),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, pixel: [u8; 4]) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(width, height, image::Rgba(pixel))
+    }
+
+    #[test]
+    fn passes_identical_images() {
+        let expected = image(4, 3, [10, 20, 30, 255]);
+        let comparison = compare(&expected, &expected.clone(), 0);
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_difference, 0);
+        assert!(
+            comparison
+                .diff_image
+                .pixels()
+                .all(|pixel| pixel.0 != [255, 0, 0, 255])
+        );
+        assert!(evaluate(&expected, &expected.clone(), 0, 0).is_ok());
+    }
+
+    #[test]
+    fn passes_differences_within_tolerance() {
+        let expected = image(4, 3, [10, 20, 30, 255]);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 1, image::Rgba([13, 20, 30, 255]));
+        let comparison = compare(&expected, &actual, 3);
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_difference, 3);
+        assert!(evaluate(&expected, &actual, 3, 0).is_ok());
+    }
+
+    #[test]
+    fn fails_differences_over_tolerance() {
+        let expected = image(4, 3, [10, 20, 30, 255]);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 1, image::Rgba([10, 20, 30, 251]));
+        actual.put_pixel(2, 0, image::Rgba([0, 20, 30, 255]));
+
+        let comparison = compare(&expected, &actual, 3);
+        assert_eq!(comparison.mismatched_pixels, 2);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.diff_image.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(comparison.diff_image.get_pixel(2, 0).0, [255, 0, 0, 255]);
+        assert_ne!(comparison.diff_image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        let mismatch = evaluate(&expected, &actual, 3, 1).unwrap_err();
+        assert!(
+            mismatch.reason.starts_with("2 pixel(s)"),
+            "{}",
+            mismatch.reason
+        );
+        assert!(mismatch.diff_image.is_some());
+        // 許容するピクセル数までなら合格
+        assert!(evaluate(&expected, &actual, 3, 2).is_ok());
+    }
+
+    #[test]
+    fn fails_size_mismatch() {
+        let expected = image(4, 3, [0, 0, 0, 255]);
+        let actual = image(3, 4, [0, 0, 0, 255]);
+        let mismatch = evaluate(&expected, &actual, 255, usize::MAX).unwrap_err();
+        assert_eq!(mismatch.reason, "size mismatch: expected 4x3, got 3x4");
+        assert!(mismatch.diff_image.is_none());
+    }
+
+    fn load_spec(name: &str, framerate: &str) -> anyhow::Result<Spec> {
+        let path = std::env::temp_dir().join(format!(
+            "vi5-render-golden-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"project": ".", "framerate": {}, "cases": []}}"#,
+                framerate
+            ),
+        )
+        .unwrap();
+        let spec = Spec::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        spec
+    }
+
+    #[test]
+    fn rejects_non_positive_framerate() {
+        assert!(load_spec("positive", "30").is_ok());
+        assert!(load_spec("zero", "0").is_err());
+        assert!(load_spec("negative", "-30").is_err());
+        assert!(crate::render::parse_framerate("NaN").is_err());
+        assert!(crate::render::parse_framerate("inf").is_err());
+    }
+}
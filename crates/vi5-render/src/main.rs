@@ -0,0 +1,235 @@
+mod golden;
+mod manifest;
+mod params;
+mod render;
+mod server;
+
+use std::collections::HashMap;
+
+use clap::Parser;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::render::RenderSettings;
+use crate::server::{Server, ServerArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Render an object to a PNG sequence
+    Render(RenderArgs),
+    /// Render the cases of a spec file and compare them with golden images
+    Test(TestArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct TestArgs {
+    /// Spec file (JSON)
+    spec: std::path::PathBuf,
+
+    /// Directory to write actual and diff images of failed frames to
+    #[clap(long, default_value = "vi5-render-diffs")]
+    diff_output: std::path::PathBuf,
+
+    /// Overwrite golden images with the rendered images instead of comparing them
+    #[clap(long)]
+    update_goldens: bool,
+
+    /// Number of frames rendered in a batch
+    #[clap(long, default_value = "8")]
+    batch_size: usize,
+
+    #[clap(flatten)]
+    server: ServerArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct RenderArgs {
+    /// vi5 project directory
+    project: std::path::PathBuf,
+
+    /// Object to render
+    #[clap(long)]
+    object: String,
+
+    /// Output directory of the PNG sequence and manifest.json
+    #[clap(long, short, default_value = "vi5-render-output")]
+    output: std::path::PathBuf,
+
+    /// First frame to render
+    #[clap(long, default_value = "0")]
+    start: usize,
+
+    /// Frame to stop rendering at (exclusive, defaults to start + 1)
+    #[clap(long)]
+    end: Option<usize>,
+
+    /// Total frames of the object (defaults to end)
+    #[clap(long)]
+    total_frames: Option<usize>,
+
+    /// Framerate
+    #[clap(long, default_value = "60", value_parser = crate::render::parse_framerate)]
+    framerate: f64,
+
+    /// Screen width
+    #[clap(long, default_value = "1920")]
+    width: usize,
+
+    /// Screen height
+    #[clap(long, default_value = "1080")]
+    height: usize,
+
+    /// Parameters as a JSON object, or @path to a JSON file
+    #[clap(long)]
+    params: Option<String>,
+
+    /// Parameter as key=value (overrides --params, can be repeated)
+    #[clap(long = "param")]
+    param: Vec<String>,
+
+    /// Object ID passed to the object
+    #[clap(long, default_value = "0")]
+    object_id: i64,
+
+    /// Seed of random()/noise() in offline renders
+    #[clap(long, default_value = "0")]
+    seed: u32,
+
+    /// Render as a preview instead of an export
+    #[clap(long)]
+    online: bool,
+
+    /// Number of frames rendered in a batch
+    #[clap(long, default_value = "8")]
+    batch_size: usize,
+
+    #[clap(flatten)]
+    server: ServerArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(
+            tracing_subscriber::EnvFilter::builder()
+                .with_default_directive(tracing_subscriber::filter::LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let args = Args::parse();
+    let succeeded = match args.command {
+        Command::Render(args) => render(args).await?,
+        Command::Test(args) => test(args).await?,
+    };
+    if !succeeded {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn read_params(
+    params: Option<&str>,
+    param: &[String],
+) -> anyhow::Result<HashMap<String, serde_json::Value>> {
+    let mut values: HashMap<String, serde_json::Value> = match params {
+        Some(path) if path.starts_with('@') => {
+            serde_json::from_str(&std::fs::read_to_string(&path[1..])?)?
+        }
+        Some(json) => serde_json::from_str(json)?,
+        None => HashMap::new(),
+    };
+    for arg in param {
+        let (key, value) = params::parse_key_value(arg)?;
+        values.insert(key, value);
+    }
+    Ok(values)
+}
+
+async fn render(args: RenderArgs) -> anyhow::Result<bool> {
+    let values = read_params(args.params.as_deref(), &args.param)?;
+    let end = args.end.unwrap_or(args.start + 1);
+    if end <= args.start {
+        anyhow::bail!("--end must be greater than --start");
+    }
+    let settings = RenderSettings {
+        screen_width: args.width,
+        screen_height: args.height,
+        framerate: args.framerate,
+        total_frames: args.total_frames.unwrap_or(end),
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        object_id: args.object_id,
+        is_offline: !args.online,
+        seed: args.seed,
+        batch_size: args.batch_size,
+    };
+
+    let mut server = Server::connect_or_spawn(&args.server).await?;
+    let result = async {
+        let project = server.initialize(&args.project).await?;
+        let object_info = project
+            .object_infos
+            .iter()
+            .find(|info| info.id == args.object)
+            .ok_or_else(|| anyhow::anyhow!("Object not found: {}", args.object))?;
+        let parameters = params::build_parameters(object_info, &values)?;
+
+        let frames = (args.start..end).collect::<Vec<_>>();
+        let rendered = render::render_frames(
+            &mut server.client,
+            &args.object,
+            &parameters,
+            &frames,
+            &settings,
+        )
+        .await?;
+
+        let manifest_frames = manifest::write_png_sequence(&args.output, &rendered)?;
+        let failed = manifest_frames
+            .iter()
+            .filter(|frame| frame.error.is_some())
+            .count();
+        manifest::Manifest::new(
+            &project.info,
+            &args.object,
+            &parameters,
+            &settings,
+            manifest_frames,
+        )
+        .write(&args.output.join("manifest.json"))?;
+        tracing::info!(
+            "Rendered {} frame(s) to {} ({} failed)",
+            rendered.len() - failed,
+            args.output.display(),
+            failed
+        );
+        anyhow::Ok(failed == 0)
+    }
+    .await;
+    server.close().await?;
+    result
+}
+
+async fn test(args: TestArgs) -> anyhow::Result<bool> {
+    let spec = golden::Spec::load(&args.spec)?;
+    let mut server = Server::connect_or_spawn(&args.server).await?;
+    let result = golden::run(
+        &mut server,
+        &spec,
+        &args.diff_output,
+        args.update_goldens,
+        args.batch_size,
+    )
+    .await;
+    server.close().await?;
+    result
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use crate::render::{RenderSettings, RenderedFrame};
+
+#[derive(serde::Serialize, Debug)]
+pub struct Manifest {
+    pub project_name: String,
+    pub renderer_version: String,
+    pub object: String,
+    pub object_id: i64,
+    pub screen_width: usize,
+    pub screen_height: usize,
+    pub framerate: f64,
+    pub total_frames: usize,
+    pub is_offline: bool,
+    pub seed: u32,
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    pub frames: Vec<ManifestFrame>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ManifestFrame {
+    pub frame: usize,
+    pub time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn frame_file_name(frame: usize) -> String {
+    format!("{:06}.png", frame)
+}
+
+/// PNG連番を`output_dir`に書き出し、その内容を表すマニフェストを返す。
+pub fn write_png_sequence(
+    output_dir: &std::path::Path,
+    frames: &[RenderedFrame],
+) -> anyhow::Result<Vec<ManifestFrame>> {
+    std::fs::create_dir_all(output_dir)?;
+    frames
+        .iter()
+        .map(|rendered| {
+            Ok(match &rendered.result {
+                Ok(image) => {
+                    let file = frame_file_name(rendered.frame);
+                    image.save_with_format(output_dir.join(&file), image::ImageFormat::Png)?;
+                    ManifestFrame {
+                        frame: rendered.frame,
+                        time: rendered.time,
+                        file: Some(file),
+                        width: Some(image.width()),
+                        height: Some(image.height()),
+                        error: None,
+                    }
+                }
+                Err(message) => ManifestFrame {
+                    frame: rendered.frame,
+                    time: rendered.time,
+                    file: None,
+                    width: None,
+                    height: None,
+                    error: Some(message.clone()),
+                },
+            })
+        })
+        .collect()
+}
+
+impl Manifest {
+    pub fn new(
+        info: &vi5_cef::InitializeResponse,
+        object: &str,
+        parameters: &[vi5_cef::Parameter],
+        settings: &RenderSettings,
+        frames: Vec<ManifestFrame>,
+    ) -> Self {
+        Self {
+            project_name: info.project_name.clone(),
+            renderer_version: info.renderer_version.clone(),
+            object: object.to_string(),
+            object_id: settings.object_id,
+            screen_width: settings.screen_width,
+            screen_height: settings.screen_height,
+            framerate: settings.framerate,
+            total_frames: settings.total_frames,
+            is_offline: settings.is_offline,
+            seed: settings.seed,
+            parameters: parameters
+                .iter()
+                .map(|parameter| {
+                    (
+                        parameter.key.clone(),
+                        crate::params::parameter_value_to_json(&parameter.value),
+                    )
+                })
+                .collect(),
+            frames,
+        }
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
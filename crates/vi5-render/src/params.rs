@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+
+/// JSONの値をパラメーター定義に従って[`vi5_cef::ParameterValue`]に変換する。
+///
+/// 色は`"#rrggbb"`、`"#rrggbbaa"`、または`{"r":..,"g":..,"b":..,"a":..}`で指定する。
+/// ベクトルと範囲は`[x, y]`のような配列、または`{"x":..,"y":..}`や`{"start":..,"end":..}`で指定する。
+pub fn parse_parameter_value(
+    definition: &vi5_cef::ParameterDefinition,
+    value: &serde_json::Value,
+) -> anyhow::Result<vi5_cef::ParameterValue> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid value for parameter {} ({:?}): {}",
+            definition.key,
+            definition.parameter_type,
+            value
+        )
+    };
+    Ok(match &definition.parameter_type {
+        vi5_cef::ParameterType::String => {
+            vi5_cef::ParameterValue::Str(value.as_str().ok_or_else(invalid)?.to_string())
+        }
+        vi5_cef::ParameterType::Text => {
+            vi5_cef::ParameterValue::Text(value.as_str().ok_or_else(invalid)?.to_string())
+        }
+        vi5_cef::ParameterType::Boolean => {
+            vi5_cef::ParameterValue::Bool(value.as_bool().ok_or_else(invalid)?)
+        }
+        vi5_cef::ParameterType::Number { min, max, .. } => {
+            let number = value.as_f64().ok_or_else(invalid)?;
+            check_range(&definition.key, number, *min, *max)?;
+            vi5_cef::ParameterValue::Number(number)
+        }
+        vi5_cef::ParameterType::Color => {
+            vi5_cef::ParameterValue::Color(parse_color(value).ok_or_else(invalid)?)
+        }
+        vi5_cef::ParameterType::Select { options } => {
+            let selected = value.as_str().ok_or_else(invalid)?;
+            if !options.iter().any(|(_, value)| value == selected) {
+                anyhow::bail!(
+                    "Parameter {} must be one of {}: {}",
+                    definition.key,
+                    options
+                        .iter()
+                        .map(|(_, value)| value.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    selected
+                );
+            }
+            vi5_cef::ParameterValue::Select(selected.to_string())
+        }
+        vi5_cef::ParameterType::File { .. } => vi5_cef::ParameterValue::File(
+            vi5_cef::FileValue::from_path(value.as_str().ok_or_else(invalid)?),
+        ),
+        vi5_cef::ParameterType::Vector2 { x, y } => {
+            let [x_value, y_value] = parse_components(value, ["x", "y"]).ok_or_else(invalid)?;
+            check_range(&definition.key, x_value, x.min, x.max)?;
+            check_range(&definition.key, y_value, y.min, y.max)?;
+            vi5_cef::ParameterValue::Vector2 {
+                x: x_value,
+                y: y_value,
+            }
+        }
+        vi5_cef::ParameterType::Vector3 { x, y, z } => {
+            let [x_value, y_value, z_value] =
+                parse_components(value, ["x", "y", "z"]).ok_or_else(invalid)?;
+            check_range(&definition.key, x_value, x.min, x.max)?;
+            check_range(&definition.key, y_value, y.min, y.max)?;
+            check_range(&definition.key, z_value, z.min, z.max)?;
+            vi5_cef::ParameterValue::Vector3 {
+                x: x_value,
+                y: y_value,
+                z: z_value,
+            }
+        }
+        vi5_cef::ParameterType::Range { start, end } => {
+            let [start_value, end_value] =
+                parse_components(value, ["start", "end"]).ok_or_else(invalid)?;
+            check_range(&definition.key, start_value, start.min, start.max)?;
+            check_range(&definition.key, end_value, end.min, end.max)?;
+            vi5_cef::ParameterValue::Range {
+                start: start_value,
+                end: end_value,
+            }
+        }
+    })
+}
+
+fn check_range(key: &str, number: f64, min: f64, max: f64) -> anyhow::Result<()> {
+    if number < min || number > max {
+        anyhow::bail!(
+            "Parameter {} is out of range ({}..={}): {}",
+            key,
+            min,
+            max,
+            number
+        );
+    }
+    Ok(())
+}
+
+fn parse_components<const N: usize>(
+    value: &serde_json::Value,
+    names: [&str; N],
+) -> Option<[f64; N]> {
+    let mut components = [0.0; N];
+    match value {
+        serde_json::Value::Array(array) if array.len() == N => {
+            for (component, item) in components.iter_mut().zip(array) {
+                *component = item.as_f64()?;
+            }
+        }
+        serde_json::Value::Object(object) => {
+            for (component, name) in components.iter_mut().zip(names) {
+                *component = object.get(name)?.as_f64()?;
+            }
+        }
+        _ => return None,
+    }
+    Some(components)
+}
+
+fn parse_color(value: &serde_json::Value) -> Option<vi5_cef::Color> {
+    match value {
+        serde_json::Value::String(hex) => {
+            let hex = hex.strip_prefix('#').unwrap_or(hex);
+            if !matches!(hex.len(), 6 | 8) {
+                return None;
+            }
+            let component = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+            Some(vi5_cef::Color {
+                r: component(0)?,
+                g: component(1)?,
+                b: component(2)?,
+                a: if hex.len() == 8 { component(3)? } else { 255 },
+            })
+        }
+        serde_json::Value::Object(object) => {
+            let component = |key: &str| u8::try_from(object.get(key)?.as_u64()?).ok();
+            Some(vi5_cef::Color {
+                r: component("r")?,
+                g: component("g")?,
+                b: component("b")?,
+                a: match object.get("a") {
+                    Some(_) => component("a")?,
+                    None => 255,
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `key=value`形式の引数を読む。値はJSONとして読めなければ文字列として扱う。
+pub fn parse_key_value(arg: &str) -> anyhow::Result<(String, serde_json::Value)> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected key=value: {}", arg))?;
+    let value = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+/// 指定された値とデフォルト値から、オブジェクトに渡すパラメーターを組み立てる。
+pub fn build_parameters(
+    object_info: &vi5_cef::ObjectInfo,
+    values: &HashMap<String, serde_json::Value>,
+) -> anyhow::Result<Vec<vi5_cef::Parameter>> {
+    for key in values.keys() {
+        if !object_info
+            .parameter_definitions
+            .iter()
+            .any(|definition| &definition.key == key)
+        {
+            anyhow::bail!("Object {} has no parameter named {}", object_info.id, key);
+        }
+    }
+
+    let mut parameters = Vec::with_capacity(object_info.parameter_definitions.len());
+    for definition in &object_info.parameter_definitions {
+        let value = match values.get(&definition.key) {
+            Some(value) => parse_parameter_value(definition, value)?,
+            None => match (&definition.default_value, &definition.parameter_type) {
+                (Some(default_value), _) => default_value.value.clone(),
+                // AviUtl2と同じく、ファイルは未選択にする
+                (None, vi5_cef::ParameterType::File { .. }) => {
+                    vi5_cef::ParameterValue::File(vi5_cef::FileValue::from_path(""))
+                }
+                (None, _) => anyhow::bail!("Missing value for parameter {}", definition.key),
+            },
+        };
+        parameters.push(vi5_cef::Parameter {
+            key: definition.key.clone(),
+            value,
+        });
+    }
+    parameters.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(parameters)
+}
+
+pub fn parameter_value_to_json(value: &vi5_cef::ParameterValue) -> serde_json::Value {
+    match value {
+        vi5_cef::ParameterValue::Str(value)
+        | vi5_cef::ParameterValue::Text(value)
+        | vi5_cef::ParameterValue::Select(value) => serde_json::Value::String(value.clone()),
+        vi5_cef::ParameterValue::Number(value) => serde_json::json!(value),
+        vi5_cef::ParameterValue::Bool(value) => serde_json::Value::Bool(*value),
+        vi5_cef::ParameterValue::File(file) => serde_json::Value::String(file.path.clone()),
+        vi5_cef::ParameterValue::Vector2 { x, y } => serde_json::json!({ "x": x, "y": y }),
+        vi5_cef::ParameterValue::Vector3 { x, y, z } => {
+            serde_json::json!({ "x": x, "y": y, "z": z })
+        }
+        vi5_cef::ParameterValue::Range { start, end } => {
+            serde_json::json!({ "start": start, "end": end })
+        }
+        vi5_cef::ParameterValue::Color(color) => serde_json::Value::String(format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.r, color.g, color.b, color.a
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(parameter_type: vi5_cef::ParameterType) -> vi5_cef::ParameterDefinition {
+        vi5_cef::ParameterDefinition {
+            key: "value".to_string(),
+            parameter_type,
+            label: "Value".to_string(),
+            default_value: None,
+            group: None,
+            description: None,
+            order: None,
+        }
+    }
+
+    fn parse(
+        parameter_type: vi5_cef::ParameterType,
+        value: serde_json::Value,
+    ) -> anyhow::Result<vi5_cef::ParameterValue> {
+        parse_parameter_value(&definition(parameter_type), &value)
+    }
+
+    fn component(min: f64, max: f64) -> vi5_cef::NumberComponent {
+        vi5_cef::NumberComponent {
+            step: vi5_cef::NumberStep::One,
+            min,
+            max,
+        }
+    }
+
+    fn color(value: serde_json::Value) -> Option<(u8, u8, u8, u8)> {
+        match parse(vi5_cef::ParameterType::Color, value) {
+            Ok(vi5_cef::ParameterValue::Color(color)) => Some((color.r, color.g, color.b, color.a)),
+            Ok(other) => panic!("unexpected value: {:?}", other),
+            Err(_) => None,
+        }
+    }
+
+    #[test]
+    fn parses_strings_and_booleans() {
+        assert!(matches!(
+            parse(vi5_cef::ParameterType::String, serde_json::json!("a")),
+            Ok(vi5_cef::ParameterValue::Str(value)) if value == "a"
+        ));
+        assert!(matches!(
+            parse(vi5_cef::ParameterType::Text, serde_json::json!("a\nb")),
+            Ok(vi5_cef::ParameterValue::Text(value)) if value == "a\nb"
+        ));
+        assert!(matches!(
+            parse(vi5_cef::ParameterType::Boolean, serde_json::json!(true)),
+            Ok(vi5_cef::ParameterValue::Bool(true))
+        ));
+        assert!(parse(vi5_cef::ParameterType::String, serde_json::json!(1)).is_err());
+        assert!(parse(vi5_cef::ParameterType::Boolean, serde_json::json!("true")).is_err());
+    }
+
+    #[test]
+    fn checks_number_range() {
+        let number = || vi5_cef::ParameterType::Number {
+            step: vi5_cef::NumberStep::PointOne,
+            min: -1.0,
+            max: 1.0,
+        };
+        assert!(matches!(
+            parse(number(), serde_json::json!(0.5)),
+            Ok(vi5_cef::ParameterValue::Number(0.5))
+        ));
+        assert!(parse(number(), serde_json::json!(-1.0)).is_ok());
+        assert!(parse(number(), serde_json::json!(1.0)).is_ok());
+        assert!(parse(number(), serde_json::json!(1.5)).is_err());
+        assert!(parse(number(), serde_json::json!(-1.5)).is_err());
+        assert!(parse(number(), serde_json::json!("0.5")).is_err());
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(
+            color(serde_json::json!("#ff8000")),
+            Some((255, 128, 0, 255))
+        );
+        assert_eq!(
+            color(serde_json::json!("FF800080")),
+            Some((255, 128, 0, 128))
+        );
+        assert_eq!(color(serde_json::json!("#ff80")), None);
+        assert_eq!(color(serde_json::json!("#gg8000")), None);
+        // 2バイト文字で区切りがずれても落ちない
+        assert_eq!(color(serde_json::json!("#ああ")), None);
+    }
+
+    #[test]
+    fn parses_object_colors() {
+        assert_eq!(
+            color(serde_json::json!({ "r": 1, "g": 2, "b": 3 })),
+            Some((1, 2, 3, 255))
+        );
+        assert_eq!(
+            color(serde_json::json!({ "r": 1, "g": 2, "b": 3, "a": 4 })),
+            Some((1, 2, 3, 4))
+        );
+        assert_eq!(color(serde_json::json!({ "r": 256, "g": 0, "b": 0 })), None);
+        assert_eq!(color(serde_json::json!({ "r": -1, "g": 0, "b": 0 })), None);
+        assert_eq!(color(serde_json::json!({ "r": 0, "g": 0 })), None);
+        assert_eq!(color(serde_json::json!(0xff8000)), None);
+    }
+
+    #[test]
+    fn accepts_only_listed_select_values() {
+        let select = || vi5_cef::ParameterType::Select {
+            options: vec![
+                ("Circle".to_string(), "circle".to_string()),
+                ("Square".to_string(), "square".to_string()),
+            ],
+        };
+        assert!(matches!(
+            parse(select(), serde_json::json!("square")),
+            Ok(vi5_cef::ParameterValue::Select(value)) if value == "square"
+        ));
+        // ラベルではなく値で指定する
+        assert!(parse(select(), serde_json::json!("Square")).is_err());
+        assert!(parse(select(), serde_json::json!("triangle")).is_err());
+    }
+
+    #[test]
+    fn parses_file_paths() {
+        let file = || vi5_cef::ParameterType::File {
+            filters: vec!["*.png".to_string()],
+        };
+        assert!(matches!(
+            parse(file(), serde_json::json!("missing.png")),
+            Ok(vi5_cef::ParameterValue::File(vi5_cef::FileValue { path, modified_ms: 0 }))
+                if path == "missing.png"
+        ));
+        assert!(parse(file(), serde_json::json!(null)).is_err());
+    }
+
+    #[test]
+    fn parses_vectors_as_arrays_and_objects() {
+        let vector2 = || vi5_cef::ParameterType::Vector2 {
+            x: component(-10.0, 10.0),
+            y: component(0.0, 1.0),
+        };
+        for value in [
+            serde_json::json!([5, 0.5]),
+            serde_json::json!({ "x": 5, "y": 0.5 }),
+        ] {
+            assert!(matches!(
+                parse(vector2(), value),
+                Ok(vi5_cef::ParameterValue::Vector2 { x: 5.0, y: 0.5 })
+            ));
+        }
+        assert!(parse(vector2(), serde_json::json!([5])).is_err());
+        assert!(parse(vector2(), serde_json::json!([5, 0.5, 1])).is_err());
+        assert!(parse(vector2(), serde_json::json!({ "x": 5 })).is_err());
+        assert!(parse(vector2(), serde_json::json!([5, "0.5"])).is_err());
+        // 成分ごとに範囲を確認する
+        assert!(parse(vector2(), serde_json::json!([5, 2])).is_err());
+        assert!(parse(vector2(), serde_json::json!([-20, 0])).is_err());
+
+        let vector3 = || vi5_cef::ParameterType::Vector3 {
+            x: component(-1.0, 1.0),
+            y: component(-1.0, 1.0),
+            z: component(-1.0, 1.0),
+        };
+        for value in [
+            serde_json::json!([0, 0.5, -1]),
+            serde_json::json!({ "x": 0, "y": 0.5, "z": -1 }),
+        ] {
+            assert!(matches!(
+                parse(vector3(), value),
+                Ok(vi5_cef::ParameterValue::Vector3 {
+                    x: 0.0,
+                    y: 0.5,
+                    z: -1.0
+                })
+            ));
+        }
+        assert!(parse(vector3(), serde_json::json!([0, 0])).is_err());
+        assert!(parse(vector3(), serde_json::json!([0, 0, 2])).is_err());
+    }
+
+    #[test]
+    fn parses_ranges_as_arrays_and_objects() {
+        let range = || vi5_cef::ParameterType::Range {
+            start: component(0.0, 100.0),
+            end: component(0.0, 100.0),
+        };
+        for value in [
+            serde_json::json!([10, 90]),
+            serde_json::json!({ "start": 10, "end": 90 }),
+        ] {
+            assert!(matches!(
+                parse(range(), value),
+                Ok(vi5_cef::ParameterValue::Range {
+                    start: 10.0,
+                    end: 90.0
+                })
+            ));
+        }
+        assert!(parse(range(), serde_json::json!({ "x": 10, "y": 90 })).is_err());
+        assert!(parse(range(), serde_json::json!([10, 120])).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub screen_width: usize,
+    pub screen_height: usize,
+    pub framerate: f64,
+    pub total_frames: usize,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub object_id: i64,
+    pub is_offline: bool,
+    pub seed: u32,
+    pub batch_size: usize,
+}
+
+pub struct RenderedFrame {
+    pub frame: usize,
+    pub time: f64,
+    pub result: Result<image::RgbaImage, String>,
+}
+
+/// フレームレートは時間の計算で割る数になるので、有限の正の値に限る。
+pub fn check_framerate(framerate: f64) -> anyhow::Result<()> {
+    if !(framerate.is_finite() && framerate > 0.0) {
+        anyhow::bail!(
+            "Framerate must be a finite number greater than 0: {}",
+            framerate
+        );
+    }
+    Ok(())
+}
+
+pub fn parse_framerate(value: &str) -> anyhow::Result<f64> {
+    let framerate = value.parse::<f64>()?;
+    check_framerate(framerate)?;
+    Ok(framerate)
+}
+
+impl RenderSettings {
+    pub fn frame_info(&self, frame: usize) -> vi5_cef::FrameInfo {
+        let time = frame as f64 / self.framerate;
+        vi5_cef::FrameInfo {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            screen_width: self.screen_width,
+            screen_height: self.screen_height,
+            current_frame: frame,
+            current_time: time,
+            total_frames: self.total_frames,
+            total_time: self.total_frames as f64 / self.framerate,
+            framerate: self.framerate,
+            global_frame: frame,
+            global_time: time,
+        }
+    }
+}
+
+/// `frames`の各フレームを`settings.batch_size`ずつまとめてレンダリングする。
+pub async fn render_frames(
+    client: &mut vi5_cef::Client,
+    object: &str,
+    parameters: &[vi5_cef::Parameter],
+    frames: &[usize],
+    settings: &RenderSettings,
+) -> anyhow::Result<Vec<RenderedFrame>> {
+    let mut rendered = Vec::with_capacity(frames.len());
+    for chunk in frames.chunks(settings.batch_size.max(1)) {
+        let requests = chunk
+            .iter()
+            .map(|&frame| vi5_cef::RenderRequest {
+                object: object.to_string(),
+                object_id: settings.object_id,
+                frame_info: settings.frame_info(frame),
+                parameters: parameters.to_vec(),
+                is_offline: settings.is_offline,
+                seed: settings.seed,
+                input_image: None,
+            })
+            .collect::<Vec<_>>();
+        let responses = client.batch_render(requests).await?;
+        for (&frame, response) in chunk.iter().zip(responses) {
+            let result = match response.response {
+                vi5_cef::RenderResponseData::Success {
+                    width,
+                    height,
+                    mut image_data,
+                    alpha_mode,
+                    color_space,
+                } => {
+                    vi5_cef::to_straight_srgb(&mut image_data, alpha_mode, color_space);
+                    image::RgbaImage::from_raw(width as _, height as _, image_data).ok_or_else(
+                        || {
+                            format!(
+                                "Invalid image data ({}x{}) returned by the server",
+                                width, height
+                            )
+                        },
+                    )
+                }
+                vi5_cef::RenderResponseData::Error(message) => Err(message),
+            };
+            if let Err(e) = &result {
+                tracing::error!("Failed to render frame {}: {}", frame, e);
+            } else {
+                tracing::debug!("Rendered frame {}", frame);
+            }
+            rendered.push(RenderedFrame {
+                frame,
+                time: frame as f64 / settings.framerate,
+                result,
+            });
+        }
+    }
+    Ok(rendered)
+}
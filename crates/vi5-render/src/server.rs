@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(60);
+const LEASE_GRACE_PERIOD: Duration = Duration::from_secs(30);
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServerArgs {
+    /// URL of a running vi5-cef-server (e.g. http://[::1]:50051)
+    #[clap(long, conflicts_with = "local_socket")]
+    server: Option<String>,
+
+    /// Local socket / named pipe of a running vi5-cef-server
+    #[clap(long)]
+    local_socket: Option<String>,
+
+    /// Token of the running vi5-cef-server
+    #[clap(long, env = "VI5_CEF_SERVER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Path to the vi5-cef-server executable to spawn (defaults to the one next to this executable)
+    #[clap(long)]
+    server_path: Option<std::path::PathBuf>,
+
+    /// Enable hardware acceleration of the spawned server
+    #[clap(long)]
+    hardware_acceleration: bool,
+}
+
+/// 接続中のvi5-cef-server。自分で起動した場合はプロセスも持つ。
+pub struct Server {
+    pub client: vi5_cef::Client,
+    child: Option<tokio::process::Child>,
+    _heartbeat: Option<vi5_cef::HeartbeatHandle>,
+}
+
+pub struct InitializedProject {
+    pub info: vi5_cef::InitializeResponse,
+    pub object_infos: Vec<vi5_cef::ObjectInfo>,
+}
+
+impl Server {
+    pub async fn connect_or_spawn(args: &ServerArgs) -> anyhow::Result<Self> {
+        if let Some(url) = &args.server {
+            tracing::info!("Connecting to vi5-cef-server at {}", url);
+            let client = Self::with_token(vi5_cef::Client::connect(url.clone()).await?, args)?;
+            return Ok(Self {
+                client,
+                child: None,
+                _heartbeat: None,
+            });
+        }
+        if let Some(path) = &args.local_socket {
+            tracing::info!("Connecting to vi5-cef-server at {}", path);
+            let client =
+                Self::with_token(vi5_cef::Client::connect_local(path.clone()).await?, args)?;
+            return Ok(Self {
+                client,
+                child: None,
+                _heartbeat: None,
+            });
+        }
+        Self::spawn(args).await
+    }
+
+    fn with_token(client: vi5_cef::Client, args: &ServerArgs) -> anyhow::Result<vi5_cef::Client> {
+        Ok(match &args.token {
+            Some(token) => client.with_token(token)?,
+            None => client,
+        })
+    }
+
+    async fn spawn(args: &ServerArgs) -> anyhow::Result<Self> {
+        let server_path = match &args.server_path {
+            Some(path) => path.clone(),
+            None => std::env::current_exe()?
+                .with_file_name(format!("vi5-cef-server{}", std::env::consts::EXE_SUFFIX)),
+        };
+        let local_socket = if cfg!(windows) {
+            format!(r"\\.\pipe\vi5-render-{}", std::process::id())
+        } else {
+            std::env::temp_dir()
+                .join(format!("vi5-render-{}.sock", std::process::id()))
+                .to_string_lossy()
+                .into_owned()
+        };
+        let token: String = rand::random::<[u8; 32]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        tracing::info!("Launching {}", server_path.display());
+        let mut command = tokio::process::Command::new(&server_path);
+        command
+            .arg("--local-socket")
+            .arg(&local_socket)
+            .arg("--parent-process")
+            .arg(std::process::id().to_string())
+            .arg("--lease-grace-period")
+            .arg(LEASE_GRACE_PERIOD.as_secs().to_string())
+            .env("VI5_CEF_SERVER_TOKEN", &token)
+            .kill_on_drop(true);
+        if args.hardware_acceleration {
+            command.arg("--hardware-acceleration");
+        }
+        // libcefなどは実行ファイルと同じディレクトリに置かれている
+        if let Some(dir) = server_path.parent() {
+            command.current_dir(dir);
+        }
+        let mut child = command.spawn().map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to launch vi5-cef-server ({}): {}",
+                server_path.display(),
+                e
+            )
+        })?;
+
+        let mut client = tokio::select! {
+            code = child.wait() => {
+                anyhow::bail!("vi5-cef-server exited during startup (exit code: {:?})", code);
+            }
+            res = vi5_cef::Client::connect_local(local_socket) => res?.with_token(&token)?,
+        };
+        let lease = client.heartbeat().await?;
+        let heartbeat =
+            client.spawn_heartbeat(lease.lease_grace_period.unwrap_or(LEASE_GRACE_PERIOD) / 3);
+        Ok(Self {
+            client,
+            child: Some(child),
+            _heartbeat: Some(heartbeat),
+        })
+    }
+
+    /// プロジェクトを初期化し、オブジェクト情報の通知が届くまで待つ。
+    pub async fn initialize(
+        &mut self,
+        project_dir: &std::path::Path,
+    ) -> anyhow::Result<InitializedProject> {
+        let project_dir = std::path::absolute(project_dir)?;
+        let info = self
+            .client
+            .initialize(
+                project_dir.to_string_lossy().into_owned(),
+                Some(INITIALIZE_TIMEOUT),
+            )
+            .await?;
+        tracing::info!(
+            "Initialized project {} (renderer {})",
+            info.project_name,
+            info.renderer_version
+        );
+
+        // 初期化時に通知の履歴は消されるので、最初に届いたオブジェクト情報がこのプロジェクトのもの
+        let mut stream = self.client.subscribe_notifications().await?;
+        let object_infos = tokio::time::timeout(INITIALIZE_TIMEOUT, async {
+            while let Some(notification) = stream.message().await? {
+                match notification {
+                    vi5_cef::Notification::ObjectInfos(notification) => {
+                        return Ok(notification.object_infos);
+                    }
+                    vi5_cef::Notification::Log(log) => match log.level {
+                        vi5_cef::LogNotificationLevel::Info => tracing::info!("{}", log.message),
+                        vi5_cef::LogNotificationLevel::Warn => tracing::warn!("{}", log.message),
+                        vi5_cef::LogNotificationLevel::Error => tracing::error!("{}", log.message),
+                    },
+                }
+            }
+            anyhow::bail!("Notification stream closed before object infos were received")
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for object infos"))??;
+
+        Ok(InitializedProject { info, object_infos })
+    }
+
+    /// 自分で起動したサーバーであれば終了させる。
+    pub async fn close(mut self) -> anyhow::Result<()> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        match self.client.shutdown(Some(SHUTDOWN_DEADLINE)).await {
+            Ok(report) => tracing::debug!("vi5-cef-server shut down: {:?}", report),
+            Err(e) => tracing::warn!("Failed to shut down vi5-cef-server: {}", e),
+        }
+        if tokio::time::timeout(SHUTDOWN_DEADLINE, child.wait())
+            .await
+            .is_err()
+        {
+            tracing::warn!("vi5-cef-server did not exit in time, killing it");
+            child.kill().await?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 /// <reference lib="dom" />
 import { vi5Log } from "./client/log";
 import { Vi5Runtime } from "./client/runtime";
+import type { CheckpointOptions } from "./client/checkpoints";
 import style from "./server/index.css?raw";
 
 // add style
@@ -13,15 +14,19 @@ declare const __vi5_data__: {
   projectName: string;
   objectList: string[];
   hookConsoleLog: boolean;
+  checkpoint: CheckpointOptions;
 };
-window.__vi5__ = new Vi5Runtime(__vi5_data__.projectName);
+window.__vi5__ = new Vi5Runtime(
+  __vi5_data__.projectName,
+  __vi5_data__.checkpoint,
+);
 const promises = [];
 for (const objectName of __vi5_data__.objectList) {
   vi5Log.info(`Loading object module: ${objectName}`);
   promises.push(
     import(/* @vite-ignore */ `${objectName}`).then((module) => {
       const object = module.default;
-      window.__vi5__.register(object);
+      window.__vi5__.register(object, module.__vi5_version);
     }),
   );
 }
@@ -0,0 +1,158 @@
+export type CheckpointOptions = {
+  interval: number;
+  memoryBudget: number;
+};
+
+type Checkpoint = {
+  key: string;
+  frame: number;
+  snapshot: unknown;
+  size: number;
+};
+
+/** スナップショットのおおよそのメモリ使用量（バイト）を見積もる。 */
+export function estimateSize(value: unknown, seen = new WeakSet<object>()): number {
+  switch (typeof value) {
+    case "number":
+    case "bigint":
+      return 8;
+    case "boolean":
+      return 4;
+    case "string":
+      return value.length * 2;
+    case "object":
+      break;
+    default:
+      return 0;
+  }
+  if (value === null || seen.has(value)) {
+    return 0;
+  }
+  seen.add(value);
+  if (value instanceof ArrayBuffer) {
+    return value.byteLength;
+  }
+  if (ArrayBuffer.isView(value)) {
+    return value.byteLength;
+  }
+  if (value instanceof ImageData) {
+    return value.data.byteLength;
+  }
+  if (value instanceof HTMLCanvasElement) {
+    return value.width * value.height * 4;
+  }
+  // p5.Graphicsなど。中身を辿るとp5のインスタンス全体を数えてしまう
+  if ("elt" in value && value.elt instanceof HTMLCanvasElement) {
+    return value.elt.width * value.elt.height * 4;
+  }
+  let size = 0;
+  if (value instanceof Map) {
+    for (const [k, v] of value) {
+      size += estimateSize(k, seen) + estimateSize(v, seen);
+    }
+    return size;
+  }
+  if (value instanceof Set) {
+    for (const v of value) {
+      size += estimateSize(v, seen);
+    }
+    return size;
+  }
+  for (const v of Object.values(value)) {
+    size += estimateSize(v, seen);
+  }
+  return size;
+}
+
+/**
+ * 状態を持つオブジェクトのチェックポイントを保持する。
+ * メモリの上限を超えたら古く作られたものから捨てる。
+ */
+export class CheckpointStore {
+  #checkpoints = new Map<string, Checkpoint[]>();
+  #insertionOrder: Checkpoint[] = [];
+  #totalSize = 0;
+  #evicted = 0;
+
+  constructor(readonly options: CheckpointOptions) {}
+
+  get count() {
+    return this.#insertionOrder.length;
+  }
+
+  get totalSize() {
+    return this.#totalSize;
+  }
+
+  get evicted() {
+    return this.#evicted;
+  }
+
+  shouldSave(key: string, frame: number): boolean {
+    return (
+      this.options.interval > 0 &&
+      frame > 0 &&
+      frame % this.options.interval === 0 &&
+      !this.#checkpoints.get(key)?.some((checkpoint) => checkpoint.frame === frame)
+    );
+  }
+
+  save(key: string, frame: number, snapshot: unknown): boolean {
+    const size = estimateSize(snapshot);
+    if (size > this.options.memoryBudget) {
+      return false;
+    }
+    const checkpoint: Checkpoint = { key, frame, snapshot, size };
+    const checkpoints = this.#checkpoints.get(key) ?? [];
+    const index = checkpoints.findIndex((c) => c.frame > frame);
+    checkpoints.splice(index === -1 ? checkpoints.length : index, 0, checkpoint);
+    this.#checkpoints.set(key, checkpoints);
+    this.#insertionOrder.push(checkpoint);
+    this.#totalSize += size;
+    while (this.#totalSize > this.options.memoryBudget) {
+      this.#remove(this.#insertionOrder[0]!);
+      this.#evicted++;
+    }
+    return true;
+  }
+
+  /** `frame`以前で最も新しいチェックポイントを返す。 */
+  nearest(key: string, frame: number): Checkpoint | undefined {
+    const checkpoints = this.#checkpoints.get(key);
+    if (!checkpoints) {
+      return undefined;
+    }
+    for (let i = checkpoints.length - 1; i >= 0; i--) {
+      if (checkpoints[i]!.frame <= frame) {
+        return checkpoints[i];
+      }
+    }
+    return undefined;
+  }
+
+  /** `key`のチェックポイントをすべて捨てる。 */
+  delete(key: string) {
+    for (const checkpoint of [...(this.#checkpoints.get(key) ?? [])]) {
+      this.#remove(checkpoint);
+    }
+  }
+
+  clear() {
+    this.#checkpoints.clear();
+    this.#insertionOrder = [];
+    this.#totalSize = 0;
+    this.#evicted = 0;
+  }
+
+  #remove(checkpoint: Checkpoint) {
+    const checkpoints = this.#checkpoints.get(checkpoint.key);
+    if (checkpoints) {
+      checkpoints.splice(checkpoints.indexOf(checkpoint), 1);
+      if (checkpoints.length === 0) {
+        this.#checkpoints.delete(checkpoint.key);
+      }
+    }
+    this.#insertionOrder.splice(this.#insertionOrder.indexOf(checkpoint), 1);
+    this.#totalSize -= checkpoint.size;
+  }
+}
@@ -0,0 +1,116 @@
+import type p5 from "p5";
+import type { FrameInfo } from "../gen/common_pb";
+
+// オフラインレンダリング（書き出し）の結果を再現可能にするための仮想時計とシード付き乱数。
+
+/** FNV-1aで文字列から32bitのシードを作る。 */
+export function hashSeed(...parts: (string | number | bigint)[]): number {
+  const input = parts.join("\0");
+  let hash = 0x811c9dc5;
+  for (let i = 0; i < input.length; i++) {
+    hash ^= input.charCodeAt(i);
+    hash = Math.imul(hash, 0x01000193);
+  }
+  return hash >>> 0;
+}
+
+/** Mulberry32 */
+function createRandom(seed: number): () => number {
+  let state = seed >>> 0;
+  return () => {
+    state = (state + 0x6d2b79f5) >>> 0;
+    let t = state;
+    t = Math.imul(t ^ (t >>> 15), t | 1);
+    t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+    return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+  };
+}
+
+/**
+ * p5の`millis()`と`random()`を、`frameInfo.currentTime`と`seed`から決まるものに差し替え、元に戻す関数を返す。
+ * このインスタンスだけに効くので、他のオブジェクトの描画とは干渉しない。
+ */
+function overrideInstance(p: p5, frameInfo: FrameInfo, seed: number): () => void {
+  const virtualNow = frameInfo.currentTime * 1000;
+  const hadOwnMillis = Object.hasOwn(p, "millis");
+  const originalMillis = p.millis;
+
+  p.millis = () => virtualNow;
+  p.randomSeed(hashSeed(seed, frameInfo.currentFrame));
+  return () => {
+    if (hadOwnMillis) {
+      p.millis = originalMillis;
+    } else {
+      delete (p as Partial<p5>).millis;
+    }
+  };
+}
+
+/**
+ * `Date.now`・`performance.now`・`Math.random`を`frameInfo.currentTime`と`seed`から決まる値に差し替え、
+ * 元に戻す関数を返す。ページ全体に効くので、差し替えている間に他の処理を挟んではいけない。
+ *
+ * 乱数はフレームごとにシードし直すので、描画の順番やバッチの区切り方に左右されない。
+ */
+function overrideGlobals(frameInfo: FrameInfo, seed: number): () => void {
+  const frameSeed = hashSeed(seed, frameInfo.currentFrame);
+  const virtualNow = frameInfo.currentTime * 1000;
+
+  const originalDateNow = Date.now;
+  const originalPerformanceNow = performance.now;
+  const originalMathRandom = Math.random;
+
+  Date.now = () => virtualNow;
+  performance.now = () => virtualNow;
+  Math.random = createRandom(frameSeed);
+  return () => {
+    Date.now = originalDateNow;
+    performance.now = originalPerformanceNow;
+    Math.random = originalMathRandom;
+  };
+}
+
+/** `fn`の実行中だけ、時計と乱数を`frameInfo`と`seed`から決まるものに差し替える。 */
+export function withDeterminism<T>(
+  p: p5,
+  frameInfo: FrameInfo,
+  seed: number,
+  fn: () => T,
+): T {
+  const restoreInstance = overrideInstance(p, frameInfo, seed);
+  const restoreGlobals = overrideGlobals(frameInfo, seed);
+  try {
+    return fn();
+  } finally {
+    restoreGlobals();
+    restoreInstance();
+  }
+}
+
+/**
+ * `withDeterminism`の非同期版。
+ *
+ * `await`の間には他のオブジェクトの描画やsetupが割り込むので、ページ全体に効く`Date.now`などは
+ * `fn`が同期的に実行される間（最初の`await`まで）だけ差し替える。
+ * p5の`millis()`と`random()`は、`fn`が返したPromiseが終わるまで差し替えたままにする。
+ */
+export async function withDeterminismAsync<T>(
+  p: p5,
+  frameInfo: FrameInfo,
+  seed: number,
+  fn: () => T | Promise<T>,
+): Promise<T> {
+  const restoreInstance = overrideInstance(p, frameInfo, seed);
+  try {
+    const restoreGlobals = overrideGlobals(frameInfo, seed);
+    let result: T | Promise<T>;
+    try {
+      result = fn();
+    } finally {
+      restoreGlobals();
+    }
+    return await result;
+  } finally {
+    restoreInstance();
+  }
+}
@@ -8,10 +8,13 @@ import {
   type ParameterDefinition as GrpcParameterDefinition,
   type ParameterType as GrpcParameterType,
   type RenderRequest,
+  type FrameInfo,
+  type FileValue,
   ParameterDefinitionSchema as GrpcParameterDefinitionSchema,
   ParameterTypeSchema,
   ParameterSchema,
   ObjectInfoSchema,
+  ObjectKind,
 } from "../gen/common_pb";
 
 import { vi5Log } from "./log";
@@ -34,6 +37,8 @@ import { packCanvases, type JsRenderResponse } from "./packCanvas";
 import p5 from "p5";
 import { DisposableCounterFactory } from "./disposableCounter";
 import { priorityLevels, RenderQueue } from "./renderQueue";
+import { hashSeed, withDeterminism, withDeterminismAsync } from "./determinism";
+import { CheckpointStore, type CheckpointOptions } from "./checkpoints";
 
 const runtimeLog = vi5Log.getChild("Vi5Runtime");
 type NotificationLevelKey = keyof typeof notificationLevelMap;
@@ -51,11 +56,21 @@ const isMessage = <Desc extends protobuf.DescMessage>(
 };
 
 // const initializePromises: Record<bigint, Promise<void>> = {};
-const initializePromises = new Map<bigint, Promise<Vi5Context>>();
-const contexts = new Map<bigint, Vi5Context>();
+const initializePromises = new Map<string, Promise<Vi5Context>>();
+const contexts = new Map<string, Vi5Context>();
+const lastDrawnFrames = new Map<string, number>();
+// 巻き戻しの再描画がこれより長いと警告する
+const replayWarningFrames = 600;
+// 再描画の間もログなどを処理できるよう、このフレーム数ごとに処理を譲る
+const replayChunkFrames = 30;
+
+// 書き出しの結果がプレビューでの状態に左右されないよう、オフライン用のコンテキストは分けておく
+function contextKey(objectId: bigint, isOffline: boolean): string {
+  return `${isOffline ? "offline" : "online"}:${objectId}`;
+}
 
 async function maybeInitializeContext<T extends ParameterDefinitions>(
-  objectId: bigint,
+  objectId: string,
   object: Vi5Object<T>,
   renderRequest: RenderRequest,
   parameter: InferParameters<T>,
@@ -77,10 +92,11 @@ async function maybeInitializeContext<T extends ParameterDefinitions>(
   }
 }
 async function initializeContext<T extends ParameterDefinitions>(
-  id: bigint,
+  id: string,
   object: Vi5Object<T>,
   renderRequest: RenderRequest,
   parameter: InferParameters<T>,
+  seed?: number,
 ): Promise<Vi5Context> {
   if (contexts.has(id)) {
     return contexts.get(id)!;
@@ -95,7 +111,15 @@ async function initializeContext<T extends ParameterDefinitions>(
     ctx.initialize(sketch);
     ctx.setFrameInfo(renderRequest.frameInfo!);
     sketch.setup = () => {
-      const setup = object.setup(ctx, ctx.p, parameter);
+      ctx.setInputImage(renderRequest.inputImage);
+      // setupが非同期でも、終わるまでp5の乱数と時計はシードしたものを使わせる
+      const setup =
+        seed === undefined
+          ? object.setup(ctx, ctx.p, parameter)
+          : withDeterminismAsync(ctx.p, renderRequest.frameInfo!, seed, () => {
+              ctx.p.noiseSeed(seed);
+              return object.setup(ctx, ctx.p, parameter);
+            });
       if (setup instanceof Promise) {
         return setup.then(() => {
           contexts.set(id, ctx);
@@ -112,6 +136,91 @@ async function initializeContext<T extends ParameterDefinitions>(
   initializePromises.set(id, promise);
   return promise;
 }
+function frameInfoAt(frameInfo: FrameInfo, frame: number): FrameInfo {
+  const offset = frame - frameInfo.currentFrame;
+  return {
+    ...frameInfo,
+    currentFrame: frame,
+    currentTime: frame / frameInfo.framerate,
+    globalFrame: frameInfo.globalFrame + offset,
+    globalTime: (frameInfo.globalFrame + offset) / frameInfo.framerate,
+  };
+}
+
+/**
+ * AviUtl2でトラックバーにならない（時間で変化しない）パラメーターのハッシュ。
+ * トラックバーはフレームごとに値が変わるので含めない。
+ */
+function staticParamsHash<T extends ParameterDefinitions>(
+  object: Vi5Object<T>,
+  params: InferParameters<T>,
+): number {
+  const staticParams: Record<string, unknown> = {};
+  for (const [key, definition] of Object.entries(object.parameters)) {
+    const value = params[key as keyof T];
+    switch (definition.type) {
+      case "number":
+      case "vector2":
+      case "vector3":
+      case "range":
+        break;
+      case "color": {
+        // アルファだけはトラックバーになる
+        const { r, g, b } = value as ParameterType<"color">;
+        staticParams[key] = { r, g, b };
+        break;
+      }
+      case "file":
+        staticParams[key] = (value as ParameterType<"file">).path;
+        break;
+      default:
+        staticParams[key] = value;
+    }
+  }
+  return hashSeed(JSON.stringify(staticParams));
+}
+
+function drawObject<T extends ParameterDefinitions>(
+  ctx: Vi5Context,
+  object: Vi5Object<T>,
+  frameInfo: FrameInfo,
+  params: InferParameters<T>,
+  seed: number | undefined,
+) {
+  ctx.setFrameInfo(frameInfo);
+  if (seed === undefined) {
+    object.draw(ctx, ctx.p, params);
+  } else {
+    withDeterminism(ctx.p, frameInfo, seed, () =>
+      object.draw(ctx, ctx.p, params),
+    );
+  }
+}
+
+// パスごとに、最後に読み込んだファイルのURL
+const fileUrls = new Map<string, { modifiedMs: bigint; url: string }>();
+
+// vi5-cef-serverはバッチ内で最初の1回だけファイルの中身を送ってくるので、ここで覚えておく
+function resolveFileUrl(file: FileValue): string {
+  if (!file.path) {
+    return "";
+  }
+  const cached = fileUrls.get(file.path);
+  if (cached && cached.modifiedMs === file.modifiedMs) {
+    return cached.url;
+  }
+  if (file.data.length === 0) {
+    runtimeLog.warn`File is not available: ${file.path}`;
+    return "";
+  }
+  if (cached) {
+    URL.revokeObjectURL(cached.url);
+  }
+  const url = URL.createObjectURL(new Blob([file.data as Uint8Array<ArrayBuffer>]));
+  fileUrls.set(file.path, { modifiedMs: file.modifiedMs, url });
+  return url;
+}
+
 function grpcParamsToJsParams<T extends ParameterDefinitions>(
   grpcParams: Parameter[],
 ): InferParameters<T> {
@@ -135,9 +244,31 @@ function grpcParamsToJsParams<T extends ParameterDefinitions>(
           r: param.value.value.r,
           g: param.value.value.g,
           b: param.value.value.b,
-          a: param.value.value.a as 0 | 255,
+          a: param.value.value.a,
+        };
+        break;
+      case "selectValue":
+        params[param.key] = param.value.value;
+        break;
+      case "fileValue":
+        params[param.key] = {
+          path: param.value.value.path,
+          url: resolveFileUrl(param.value.value),
+        };
+        break;
+      case "vector2Value":
+        params[param.key] = { x: param.value.value.x, y: param.value.value.y };
+        break;
+      case "vector3Value":
+        params[param.key] = {
+          x: param.value.value.x,
+          y: param.value.value.y,
+          z: param.value.value.z,
         };
         break;
+      case "rangeValue":
+        params[param.key] = { start: param.value.value.start, end: param.value.value.end };
+        break;
       default:
         runtimeLog.warn`Unknown parameter value case: ${param.value.case satisfies undefined}`;
     }
@@ -188,6 +319,55 @@ function toGrpcParameterType(
           value: {},
         },
       });
+    case "file":
+      return protobuf.create(ParameterTypeSchema, {
+        kind: {
+          case: "file",
+          value: {
+            filters: definition.filters ?? [],
+          },
+        },
+      });
+    case "select":
+      return protobuf.create(ParameterTypeSchema, {
+        kind: {
+          case: "select",
+          value: {
+            options: definition.options,
+          },
+        },
+      });
+    case "vector2":
+      return protobuf.create(ParameterTypeSchema, {
+        kind: {
+          case: "vector2",
+          value: {
+            x: definition.x,
+            y: definition.y,
+          },
+        },
+      });
+    case "vector3":
+      return protobuf.create(ParameterTypeSchema, {
+        kind: {
+          case: "vector3",
+          value: {
+            x: definition.x,
+            y: definition.y,
+            z: definition.z,
+          },
+        },
+      });
+    case "range":
+      return protobuf.create(ParameterTypeSchema, {
+        kind: {
+          case: "range",
+          value: {
+            start: definition.start,
+            end: definition.end,
+          },
+        },
+      });
   }
 }
 
@@ -195,7 +375,8 @@ function toGrpcDefaultValue(
   key: string,
   definition: ParameterDefinitions[string],
 ): Parameter | undefined {
-  if (definition.default === undefined) {
+  // ファイルは最初に選ぶものを指定できない
+  if (definition.type === "file" || definition.default === undefined) {
     return undefined;
   }
   switch (definition.type) {
@@ -232,6 +413,35 @@ function toGrpcDefaultValue(
           },
         },
       });
+    case "select":
+      return protobuf.create(ParameterSchema, {
+        key,
+        value: { case: "selectValue", value: definition.default },
+      });
+    case "vector2":
+      return protobuf.create(ParameterSchema, {
+        key,
+        value: {
+          case: "vector2Value",
+          value: { x: definition.default.x, y: definition.default.y },
+        },
+      });
+    case "vector3":
+      return protobuf.create(ParameterSchema, {
+        key,
+        value: {
+          case: "vector3Value",
+          value: { x: definition.default.x, y: definition.default.y, z: definition.default.z },
+        },
+      });
+    case "range":
+      return protobuf.create(ParameterSchema, {
+        key,
+        value: {
+          case: "rangeValue",
+          value: { start: definition.default.start, end: definition.default.end },
+        },
+      });
   }
 }
 
@@ -244,6 +454,9 @@ function toGrpcParameterDefinition(
     label: definition.label ?? key,
     type: toGrpcParameterType(definition),
     defaultValue: toGrpcDefaultValue(key, definition),
+    group: definition.group ?? "",
+    description: definition.description ?? "",
+    order: definition.order,
   });
 }
 
@@ -276,13 +489,27 @@ export class Vi5Runtime {
   readonly canvas: HTMLCanvasElement;
   readonly ctx: CanvasRenderingContext2D;
   readonly objects = new Map<string, Vi5Object<ParameterDefinitions>>();
+  readonly objectVersions = new Map<string, string>();
   #renderQueue = new RenderQueue();
   #notifyCounter = new DisposableCounterFactory();
   #logQueue: { level: NotificationLevelKey; message: string }[] = [];
+  #checkpoints: CheckpointStore;
+  // 最後に描画したフレームを描く直前の状態。パラメーターの変更などで同じフレームを描き直すときに戻す
+  #redrawSnapshots = new Map<string, { frame: number; snapshot: unknown }>();
+  // チェックポイントを作ったときの、時間で変化しないパラメーターのハッシュ
+  #checkpointParams = new Map<string, number>();
+  // フレームごとに描画したときのパラメーター。巻き戻して描き直すときに、トラックバーの値を再現する
+  #frameParams = new Map<string, Map<number, unknown>>();
+  // 前回のプレビュー以降に書き出しで描画したオフラインのコンテキスト
+  #offlineSessionKeys = new Set<string>();
 
-  constructor(public projectName: string) {
+  constructor(
+    public projectName: string,
+    checkpointOptions: CheckpointOptions,
+  ) {
     this.canvas = document.getElementById("vi5-canvas") as HTMLCanvasElement;
     this.ctx = this.canvas.getContext("2d")!;
+    this.#checkpoints = new CheckpointStore(checkpointOptions);
   }
 
   async init() {
@@ -315,6 +542,16 @@ export class Vi5Runtime {
           protobuf.create(ObjectInfoSchema, {
             id: obj.id,
             label: obj.label,
+            stateful: obj.stateful ?? false,
+            kind:
+              obj.kind === "filter" ? ObjectKind.FILTER : ObjectKind.GENERATOR,
+            version: this.objectVersions.get(obj.id) ?? "",
+            dependencies: obj.dependsOn && {
+              time: obj.dependsOn.time ?? true,
+              position: obj.dependsOn.position ?? true,
+              screenSize: obj.dependsOn.screenSize ?? true,
+              parameters: obj.dependsOn.parameters ?? true,
+            },
             parameterDefinitions: Object.entries(obj.parameters).map(
               ([key, def]) => toGrpcParameterDefinition(key, def),
             ),
@@ -343,6 +580,14 @@ export class Vi5Runtime {
     try {
       const data = await fastBase64.toBytes(dataB64);
       const renderPayload = protobuf.fromBinary(BatchRenderRequestSchema, data);
+      // 描画に失敗したリクエストにだけファイルの中身が入っていても読めるよう、先に全部覚えておく
+      for (const req of renderPayload.renderRequests) {
+        for (const param of req.parameters) {
+          if (param.value.case === "fileValue") {
+            resolveFileUrl(param.value.value);
+          }
+        }
+      }
       const jsResponses: JsRenderResponse[] = [];
 
       for (const req of renderPayload.renderRequests) {
@@ -430,6 +675,174 @@ export class Vi5Runtime {
     }
     contexts.clear();
     initializePromises.clear();
+    lastDrawnFrames.clear();
+    this.#checkpoints.clear();
+    this.#redrawSnapshots.clear();
+    this.#checkpointParams.clear();
+    this.#frameParams.clear();
+    this.#offlineSessionKeys.clear();
+  }
+
+  /**
+   * 状態を持つオブジェクトのために、前回描画したフレームから要求されたフレームの直前までを描画する。
+   * 途中のフレームの画像は送らない。
+   * 近いチェックポイントがあればそこから、巻き戻った場合でチェックポイントがなければ
+   * コンテキストを作り直して0フレーム目から描画し直す。
+   * 最後に描画したフレームをもう一度描くときは、そのフレームを描く直前の状態に戻すだけにする。
+   */
+  private async catchUp<T extends ParameterDefinitions>(
+    key: string,
+    ctx: Vi5Context,
+    object: Vi5Object<T>,
+    request: RenderRequest,
+    params: InferParameters<T>,
+    seed: number | undefined,
+  ): Promise<Vi5Context> {
+    const frameInfo = request.frameInfo!;
+    const target = frameInfo.currentFrame;
+    let lastFrame = lastDrawnFrames.get(key);
+    if (lastFrame === target) {
+      const redraw = this.#redrawSnapshots.get(key);
+      if (redraw?.frame === target && object.restore) {
+        object.restore(ctx, ctx.p, redraw.snapshot);
+      } else {
+        runtimeLog.debug`Redrawing frame ${target} of ${request.object} over its current state`;
+      }
+      return ctx;
+    }
+    const isBackwards = lastFrame !== undefined && target < lastFrame;
+    if (isBackwards) {
+      lastFrame = undefined;
+    }
+
+    const checkpoint = object.restore
+      ? this.#checkpoints.nearest(key, target - 1)
+      : undefined;
+    let restoredFrame: number | undefined;
+    if (checkpoint && checkpoint.frame > (lastFrame ?? -1)) {
+      object.restore!(ctx, ctx.p, checkpoint.snapshot);
+      lastFrame = checkpoint.frame;
+      restoredFrame = checkpoint.frame;
+    } else if (isBackwards) {
+      runtimeLog.info`Seeking backwards on ${request.object} without a checkpoint, restarting from frame 0`;
+      this.#discardContext(key, ctx, false);
+      ctx = await initializeContext(
+        key,
+        object,
+        { ...request, frameInfo: frameInfoAt(frameInfo, 0) },
+        params,
+        seed,
+      );
+    }
+
+    const from = lastFrame === undefined ? 0 : lastFrame + 1;
+    if (target - from > replayWarningFrames) {
+      runtimeLog.warn`Replaying ${target - from} frames of ${request.object} to reach frame ${target}; set snapshot/restore to make seeking faster`;
+    }
+    const frameParams = this.#frameParams.get(key);
+    let unknownFrames = 0;
+    for (let frame = from; frame < target; frame++) {
+      if (frame > from && (frame - from) % replayChunkFrames === 0) {
+        await new Promise((resolve) => setTimeout(resolve, 0));
+      }
+      // まだ描画していないフレームのトラックバーの値は分からないので、要求されたフレームの値で代用する
+      const replayParams = frameParams?.get(frame) as InferParameters<T> | undefined;
+      if (!replayParams) {
+        unknownFrames++;
+      }
+      drawObject(ctx, object, frameInfoAt(frameInfo, frame), replayParams ?? params, seed);
+      this.#maybeSaveCheckpoint(key, ctx, object, frame);
+    }
+    const replayed = Math.max(target - from, 0);
+    if (unknownFrames > 0) {
+      runtimeLog.debug`Replayed ${unknownFrames} frame(s) of ${request.object} with the parameters of frame ${target}`;
+    }
+    if (restoredFrame !== undefined || replayed > 0) {
+      const restored =
+        restoredFrame === undefined
+          ? `no checkpoint restored`
+          : `restored checkpoint at frame ${restoredFrame}`;
+      this.pushLog(
+        "info",
+        `${request.object}: ${restored}, replayed ${replayed} frame(s) to reach frame ${target} ` +
+          `(checkpoints: ${this.#checkpoints.count}, ${Math.round(this.#checkpoints.totalSize / 1024)} KiB, ` +
+          `evicted: ${this.#checkpoints.evicted})`,
+      );
+    }
+    return ctx;
+  }
+
+  /**
+   * 新しい書き出しが始まっていたら、前の書き出しで使ったオフラインのコンテキストを捨てる。
+   * 書き出し中はオブジェクトのフレームが1つずつ順に要求されるので、プレビューを挟んだ後の最初の要求と、
+   * 前に描画したフレームの続きでない要求を、新しい書き出しの始まりとみなす。
+   */
+  #resetOfflineContextOnNewExport(key: string, request: RenderRequest) {
+    const previousContext = contexts.get(key);
+    const isContinuation =
+      this.#offlineSessionKeys.has(key) &&
+      lastDrawnFrames.get(key) === request.frameInfo!.currentFrame - 1;
+    this.#offlineSessionKeys.add(key);
+    if (previousContext && !isContinuation) {
+      runtimeLog.info`Resetting the offline context of ${request.object} for a new export`;
+      this.#discardContext(key, previousContext, true);
+    }
+  }
+
+  /** コンテキストを捨てる。`clearCheckpoints`ならチェックポイントも捨てる。 */
+  #discardContext(key: string, ctx: Vi5Context, clearCheckpoints: boolean) {
+    ctx.teardown();
+    contexts.delete(key);
+    initializePromises.delete(key);
+    lastDrawnFrames.delete(key);
+    this.#redrawSnapshots.delete(key);
+    if (clearCheckpoints) {
+      this.#checkpoints.delete(key);
+      this.#checkpointParams.delete(key);
+      this.#frameParams.delete(key);
+    }
+  }
+
+  /**
+   * 時間で変化しないパラメーターが前回と変わっていたら、前のパラメーターで作ったチェックポイントと
+   * フレームごとのパラメーターを捨てる。トラックバーはフレームごとに変わるので比べない。
+   */
+  #invalidateCheckpointsOnParamsChange<T extends ParameterDefinitions>(
+    key: string,
+    object: Vi5Object<T>,
+    params: InferParameters<T>,
+  ) {
+    const paramsHash = staticParamsHash(object, params);
+    const previous = this.#checkpointParams.get(key);
+    if (previous !== undefined && previous !== paramsHash) {
+      runtimeLog.debug`Parameters of ${object.id} changed, discarding its checkpoints`;
+      this.#checkpoints.delete(key);
+      this.#frameParams.delete(key);
+    }
+    this.#checkpointParams.set(key, paramsHash);
+  }
+
+  #recordFrameParams(key: string, frame: number, params: unknown) {
+    let frameParams = this.#frameParams.get(key);
+    if (!frameParams) {
+      frameParams = new Map();
+      this.#frameParams.set(key, frameParams);
+    }
+    frameParams.set(frame, params);
+  }
+
+  #maybeSaveCheckpoint<T extends ParameterDefinitions>(
+    key: string,
+    ctx: Vi5Context,
+    object: Vi5Object<T>,
+    frame: number,
+  ) {
+    if (!object.snapshot || !this.#checkpoints.shouldSave(key, frame)) {
+      return;
+    }
+    if (!this.#checkpoints.save(key, frame, object.snapshot(ctx, ctx.p))) {
+      runtimeLog.warn`Snapshot of frame ${frame} does not fit in the checkpoint memory budget`;
+    }
   }
 
   private async doRender(request: RenderRequest): Promise<JsRenderResponse> {
@@ -444,9 +857,19 @@ export class Vi5Runtime {
     }
 
     const params = grpcParamsToJsParams(request.parameters);
-    const ctx = request.isOffline
-      ? await initializeContext(request.objectId, object, request, params)
-      : await maybeInitializeContext(request.objectId, object, request, params);
+    const key = contextKey(request.objectId, request.isOffline);
+    const seed = request.isOffline
+      ? hashSeed(request.objectId, request.seed)
+      : undefined;
+    // 書き出しごとに同じ結果になるよう、新しい書き出しでは前の書き出しのコンテキストを作り直す
+    if (request.isOffline) {
+      this.#resetOfflineContextOnNewExport(key, request);
+    } else {
+      this.#offlineSessionKeys.clear();
+    }
+    let ctx = request.isOffline
+      ? await initializeContext(key, object, request, params, seed)
+      : await maybeInitializeContext(key, object, request, params);
     if (!ctx) {
       runtimeLog.info`Object not initialized yet: ${request.object}`;
       return {
@@ -455,8 +878,24 @@ export class Vi5Runtime {
         error: `Object not initialized yet: ${request.object}`,
       };
     }
-    ctx.setFrameInfo(request.frameInfo!);
-    object.draw(ctx, ctx.p, params);
+    ctx.setInputImage(request.inputImage);
+    if (object.stateful) {
+      this.#invalidateCheckpointsOnParamsChange(key, object, params);
+      ctx = await this.catchUp(key, ctx, object, request, params, seed);
+    }
+    // 書き出しは順に進むだけなので、プレビューのときだけ描き直し用に状態を取っておく
+    if (object.stateful && object.snapshot && object.restore && !request.isOffline) {
+      this.#redrawSnapshots.set(key, {
+        frame: request.frameInfo!.currentFrame,
+        snapshot: object.snapshot(ctx, ctx.p),
+      });
+    }
+    drawObject(ctx, object, request.frameInfo!, params, seed);
+    lastDrawnFrames.set(key, request.frameInfo!.currentFrame);
+    if (object.stateful) {
+      this.#recordFrameParams(key, request.frameInfo!.currentFrame, params);
+      this.#maybeSaveCheckpoint(key, ctx, object, request.frameInfo!.currentFrame);
+    }
     const p5Canvas = ctx.mainCanvas;
     return {
       type: "success",
@@ -562,9 +1001,10 @@ export class Vi5Runtime {
     return window.__vi5__;
   }
 
-  register<T extends Vi5Object<ParameterDefinitions>>(object: T) {
+  register<T extends Vi5Object<ParameterDefinitions>>(object: T, version = "") {
     runtimeLog.info`Registering object: ${object.id} (${object.label})`;
     this.objects.set(object.id, object);
+    this.objectVersions.set(object.id, version);
 
     this.purgeCache();
 
@@ -573,6 +1013,7 @@ export class Vi5Runtime {
   unregister(id: string) {
     runtimeLog.info`Unregistering object: ${id}`;
     this.objects.delete(id);
+    this.objectVersions.delete(id);
 
     this.#notifyObjectInfos();
   }
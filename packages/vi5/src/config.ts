@@ -5,6 +5,13 @@ export interface Config {
   vite?: ViteConfig;
   vitePlugins?: PluginOption[];
   hookConsoleLog?: boolean;
+  /** stateful なオブジェクトのチェックポイントの設定 */
+  checkpoint?: {
+    /** 何フレームごとにチェックポイントを作るか（0で無効、デフォルト: 60） */
+    interval?: number;
+    /** チェックポイントに使うメモリの上限（バイト、デフォルト: 256MiB） */
+    memoryBudget?: number;
+  };
 }
 
 type ConfigExport = Config | (() => Config) | Promise<Config> | (() => Promise<Config>);
@@ -11,4 +11,5 @@ declare const __vi5_data__: {
   projectName: string;
   objectList: string[];
   hookConsoleLog: boolean;
+  checkpoint: import("./client/checkpoints").CheckpointOptions;
 };
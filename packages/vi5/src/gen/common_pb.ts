@@ -12,7 +12,7 @@ import type { Message } from "@bufbuild/protobuf";
  * Describes the file common.proto.
  */
 export const file_common: GenFile = /*@__PURE__*/
-  fileDesc("Cgxjb21tb24ucHJvdG8SBmNvbW1vbiIGCgRWb2lkIqoBCg1SZW5kZXJSZXF1ZXN0EhQKDHJlbmRlcl9ub25jZRgBIAEoBRIOCgZvYmplY3QYAiABKAkSEQoJb2JqZWN0X2lkGAMgASgDEiUKCmZyYW1lX2luZm8YBCABKAsyES5jb21tb24uRnJhbWVJbmZvEiUKCnBhcmFtZXRlcnMYBSADKAsyES5jb21tb24uUGFyYW1ldGVyEhIKCmlzX29mZmxpbmUYBiABKAgi7gEKCUZyYW1lSW5mbxIJCgF4GAEgASgBEgkKAXkYAiABKAESCQoBehgDIAEoARIUCgxzY3JlZW5fd2lkdGgYBCABKAUSFQoNc2NyZWVuX2hlaWdodBgFIAEoBRIVCg1jdXJyZW50X2ZyYW1lGAYgASgFEhQKDGN1cnJlbnRfdGltZRgHIAEoARIUCgx0b3RhbF9mcmFtZXMYCCABKAUSEgoKdG90YWxfdGltZRgJIAEoARIRCglmcmFtZXJhdGUYCiABKAESFAoMZ2xvYmFsX2ZyYW1lGAsgASgFEhMKC2dsb2JhbF90aW1lGAwgASgBIqABCglQYXJhbWV0ZXISCwoDa2V5GAEgASgJEhMKCXN0cl92YWx1ZRgCIAEoCUgAEhQKCnRleHRfdmFsdWUYAyABKAlIABIWCgxudW1iZXJfdmFsdWUYBCABKAFIABIUCgpib29sX3ZhbHVlGAUgASgISAASJAoLY29sb3JfdmFsdWUYBiABKAsyDS5jb21tb24uQ29sb3JIAEIHCgV2YWx1ZSIzCgVDb2xvchIJCgFyGAEgASgNEgkKAWcYAiABKA0SCQoBYhgDIAEoDRIJCgFhGAQgASgNImMKCk9iamVjdEluZm8SCgoCaWQYASABKAkSDQoFbGFiZWwYAiABKAkSOgoVcGFyYW1ldGVyX2RlZmluaXRpb25zGAMgAygLMhsuY29tbW9uLlBhcmFtZXRlckRlZmluaXRpb24iEQoPUGFyYW1ldGVyU3RyaW5nIg8KDVBhcmFtZXRlclRleHQiEgoQUGFyYW1ldGVyQm9vbGVhbiJNCg9QYXJhbWV0ZXJOdW1iZXISIAoEc3RlcBgBIAEoDjISLmNvbW1vbi5OdW1iZXJTdGVwEgsKA21pbhgCIAEoARILCgNtYXgYAyABKAEiEAoOUGFyYW1ldGVyQ29sb3Ii6gEKDVBhcmFtZXRlclR5cGUSKQoGc3RyaW5nGAEgASgLMhcuY29tbW9uLlBhcmFtZXRlclN0cmluZ0gAEiUKBHRleHQYAiABKAsyFS5jb21tb24uUGFyYW1ldGVyVGV4dEgAEisKB2Jvb2xlYW4YAyABKAsyGC5jb21tb24uUGFyYW1ldGVyQm9vbGVhbkgAEikKBm51bWJlchgEIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJOdW1iZXJIABInCgVjb2xvchgFIAEoCzIWLmNvbW1vbi5QYXJhbWV0ZXJDb2xvckgAQgYKBGtpbmQigAEKE1BhcmFtZXRlckRlZmluaXRpb24SCwoDa2V5GAEgASgJEiMKBHR5cGUYAiABKAsyFS5jb21tb24uUGFyYW1ldGVyVHlwZRINCgVsYWJlbBgDIAEoCRIoCg1kZWZhdWx0X3ZhbHVlGAQgASgLMhEuY29tbW9uLlBhcmFtZXRlciJEChJCYXRjaFJlbmRlclJlcXVlc3QSLgoPcmVuZGVyX3JlcXVlc3RzGAEgAygLMhUuY29tbW9uLlJlbmRlclJlcXVlc3QqgQEKCk51bWJlclN0ZXASEwoPTlVNQkVSX1NURVBfT05FEAASGQoVTlVNQkVSX1NURVBfUE9JTlRfT05FEAESHgoaTlVNQkVSX1NURVBfUE9JTlRfWkVST19PTkUQAhIjCh9OVU1CRVJfU1RFUF9QT0lOVF9aRVJPX1pFUk9fT05FEANiBnByb3RvMw");
+  fileDesc("Cgxjb21tb24ucHJvdG8SBmNvbW1vbiIGCgRWb2lkIvYBCg1SZW5kZXJSZXF1ZXN0EhQKDHJlbmRlcl9ub25jZRgBIAEoBRIOCgZvYmplY3QYAiABKAkSEQoJb2JqZWN0X2lkGAMgASgDEiUKCmZyYW1lX2luZm8YBCABKAsyES5jb21tb24uRnJhbWVJbmZvEiUKCnBhcmFtZXRlcnMYBSADKAsyES5jb21tb24uUGFyYW1ldGVyEhIKCmlzX29mZmxpbmUYBiABKAgSDAoEc2VlZBgHIAEoDRIsCgtpbnB1dF9pbWFnZRgIIAEoCzISLmNvbW1vbi5JbnB1dEltYWdlSACIAQFCDgoMX2lucHV0X2ltYWdlIj8KCklucHV0SW1hZ2USDQoFd2lkdGgYASABKAUSDgoGaGVpZ2h0GAIgASgFEhIKCmltYWdlX2RhdGEYAyABKAwi7gEKCUZyYW1lSW5mbxIJCgF4GAEgASgBEgkKAXkYAiABKAESCQoBehgDIAEoARIUCgxzY3JlZW5fd2lkdGgYBCABKAUSFQoNc2NyZWVuX2hlaWdodBgFIAEoBRIVCg1jdXJyZW50X2ZyYW1lGAYgASgFEhQKDGN1cnJlbnRfdGltZRgHIAEoARIUCgx0b3RhbF9mcmFtZXMYCCABKAUSEgoKdG90YWxfdGltZRgJIAEoARIRCglmcmFtZXJhdGUYCiABKAESFAoMZ2xvYmFsX2ZyYW1lGAsgASgFEhMKC2dsb2JhbF90aW1lGAwgASgBItsCCglQYXJhbWV0ZXISCwoDa2V5GAEgASgJEhMKCXN0cl92YWx1ZRgCIAEoCUgAEhQKCnRleHRfdmFsdWUYAyABKAlIABIWCgxudW1iZXJfdmFsdWUYBCABKAFIABIUCgpib29sX3ZhbHVlGAUgASgISAASJAoLY29sb3JfdmFsdWUYBiABKAsyDS5jb21tb24uQ29sb3JIABIWCgxzZWxlY3RfdmFsdWUYByABKAlIABInCgpmaWxlX3ZhbHVlGAggASgLMhEuY29tbW9uLkZpbGVWYWx1ZUgAEigKDXZlY3RvcjJfdmFsdWUYCSABKAsyDy5jb21tb24uVmVjdG9yMkgAEigKDXZlY3RvcjNfdmFsdWUYCiABKAsyDy5jb21tb24uVmVjdG9yM0gAEiQKC3JhbmdlX3ZhbHVlGAsgASgLMg0uY29tbW9uLlJhbmdlSABCBwoFdmFsdWUiHwoHVmVjdG9yMhIJCgF4GAEgASgBEgkKAXkYAiABKAEiKgoHVmVjdG9yMxIJCgF4GAEgASgBEgkKAXkYAiABKAESCQoBehgDIAEoASIjCgVSYW5nZRINCgVzdGFydBgBIAEoARILCgNlbmQYAiABKAEiPAoJRmlsZVZhbHVlEgwKBHBhdGgYASABKAkSEwoLbW9kaWZpZWRfbXMYAiABKAMSDAoEZGF0YRgDIAEoDCIzCgVDb2xvchIJCgFyGAEgASgNEgkKAWcYAiABKA0SCQoBYhgDIAEoDRIJCgFhGAQgASgNIvABCgpPYmplY3RJbmZvEgoKAmlkGAEgASgJEg0KBWxhYmVsGAIgASgJEjoKFXBhcmFtZXRlcl9kZWZpbml0aW9ucxgDIAMoCzIbLmNvbW1vbi5QYXJhbWV0ZXJEZWZpbml0aW9uEhAKCHN0YXRlZnVsGAQgASgIEg8KB3ZlcnNpb24YBSABKAkSNQoMZGVwZW5kZW5jaWVzGAYgASgLMhouY29tbW9uLk9iamVjdERlcGVuZGVuY2llc0gAiAEBEiAKBGtpbmQYByABKA4yEi5jb21tb24uT2JqZWN0S2luZEIPCg1fZGVwZW5kZW5jaWVzIl0KEk9iamVjdERlcGVuZGVuY2llcxIMCgR0aW1lGAEgASgIEhAKCHBvc2l0aW9uGAIgASgIEhMKC3NjcmVlbl9zaXplGAMgASgIEhIKCnBhcmFtZXRlcnMYBCABKAgiEQoPUGFyYW1ldGVyU3RyaW5nIg8KDVBhcmFtZXRlclRleHQiEgoQUGFyYW1ldGVyQm9vbGVhbiJNCg9QYXJhbWV0ZXJOdW1iZXISIAoEc3RlcBgBIAEoDjISLmNvbW1vbi5OdW1iZXJTdGVwEgsKA21pbhgCIAEoARILCgNtYXgYAyABKAEiEAoOUGFyYW1ldGVyQ29sb3IiLAoMU2VsZWN0T3B0aW9uEg0KBWxhYmVsGAEgASgJEg0KBXZhbHVlGAIgASgJIjgKD1BhcmFtZXRlclNlbGVjdBIlCgdvcHRpb25zGAEgAygLMhQuY29tbW9uLlNlbGVjdE9wdGlvbiJaChBQYXJhbWV0ZXJWZWN0b3IyEiIKAXgYASABKAsyFy5jb21tb24uUGFyYW1ldGVyTnVtYmVyEiIKAXkYAiABKAsyFy5jb21tb24uUGFyYW1ldGVyTnVtYmVyIn4KEFBhcmFtZXRlclZlY3RvcjMSIgoBeBgBIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJOdW1iZXISIgoBeRgCIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJOdW1iZXISIgoBehgDIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJOdW1iZXIiXgoOUGFyYW1ldGVyUmFuZ2USJgoFc3RhcnQYASABKAsyFy5jb21tb24uUGFyYW1ldGVyTnVtYmVyEiQKA2VuZBgCIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJOdW1iZXIiIAoNUGFyYW1ldGVyRmlsZRIPCgdmaWx0ZXJzGAEgAygJIr8DCg1QYXJhbWV0ZXJUeXBlEikKBnN0cmluZxgBIAEoCzIXLmNvbW1vbi5QYXJhbWV0ZXJTdHJpbmdIABIlCgR0ZXh0GAIgASgLMhUuY29tbW9uLlBhcmFtZXRlclRleHRIABIrCgdib29sZWFuGAMgASgLMhguY29tbW9uLlBhcmFtZXRlckJvb2xlYW5IABIpCgZudW1iZXIYBCABKAsyFy5jb21tb24uUGFyYW1ldGVyTnVtYmVySAASJwoFY29sb3IYBSABKAsyFi5jb21tb24uUGFyYW1ldGVyQ29sb3JIABIpCgZzZWxlY3QYBiABKAsyFy5jb21tb24uUGFyYW1ldGVyU2VsZWN0SAASJQoEZmlsZRgHIAEoCzIVLmNvbW1vbi5QYXJhbWV0ZXJGaWxlSAASKwoHdmVjdG9yMhgIIAEoCzIYLmNvbW1vbi5QYXJhbWV0ZXJWZWN0b3IySAASKwoHdmVjdG9yMxgJIAEoCzIYLmNvbW1vbi5QYXJhbWV0ZXJWZWN0b3IzSAASJwoFcmFuZ2UYCiABKAsyFi5jb21tb24uUGFyYW1ldGVyUmFuZ2VIAEIGCgRraW5kIsIBChNQYXJhbWV0ZXJEZWZpbml0aW9uEgsKA2tleRgBIAEoCRIjCgR0eXBlGAIgASgLMhUuY29tbW9uLlBhcmFtZXRlclR5cGUSDQoFbGFiZWwYAyABKAkSKAoNZGVmYXVsdF92YWx1ZRgEIAEoCzIRLmNvbW1vbi5QYXJhbWV0ZXISDQoFZ3JvdXAYBSABKAkSEwoLZGVzY3JpcHRpb24YBiABKAkSEgoFb3JkZXIYByABKAVIAIgBAUIICgZfb3JkZXIiRAoSQmF0Y2hSZW5kZXJSZXF1ZXN0Ei4KD3JlbmRlcl9yZXF1ZXN0cxgBIAMoCzIVLmNvbW1vbi5SZW5kZXJSZXF1ZXN0Kj8KCk9iamVjdEtpbmQSGQoVT0JKRUNUX0tJTkRfR0VORVJBVE9SEAASFgoST0JKRUNUX0tJTkRfRklMVEVSEAEqgQEKCk51bWJlclN0ZXASEwoPTlVNQkVSX1NURVBfT05FEAASGQoVTlVNQkVSX1NURVBfUE9JTlRfT05FEAESHgoaTlVNQkVSX1NURVBfUE9JTlRfWkVST19PTkUQAhIjCh9OVU1CRVJfU1RFUF9QT0lOVF9aRVJPX1pFUk9fT05FEANiBnByb3RvMw");
 
 /**
  * @generated from message common.Void
@@ -60,6 +60,20 @@ export type RenderRequest = Message<"common.RenderRequest"> & {
    * @generated from field: bool is_offline = 6;
    */
   isOffline: boolean;
+
+  /**
+   * Mixed with object_id to seed random()/noise() in offline renders.
+   *
+   * @generated from field: uint32 seed = 7;
+   */
+  seed: number;
+
+  /**
+   * The image of the object a filter is applied to. Only set for filter objects.
+   *
+   * @generated from field: optional common.InputImage input_image = 8;
+   */
+  inputImage?: InputImage;
 };
 
 /**
@@ -69,6 +83,35 @@ export type RenderRequest = Message<"common.RenderRequest"> & {
 export const RenderRequestSchema: GenMessage<RenderRequest> = /*@__PURE__*/
   messageDesc(file_common, 1);
 
+/**
+ * @generated from message common.InputImage
+ */
+export type InputImage = Message<"common.InputImage"> & {
+  /**
+   * @generated from field: int32 width = 1;
+   */
+  width: number;
+
+  /**
+   * @generated from field: int32 height = 2;
+   */
+  height: number;
+
+  /**
+   * RGBA8, row-major, not premultiplied.
+   *
+   * @generated from field: bytes image_data = 3;
+   */
+  imageData: Uint8Array;
+};
+
+/**
+ * Describes the message common.InputImage.
+ * Use `create(InputImageSchema)` to create a new message.
+ */
+export const InputImageSchema: GenMessage<InputImage> = /*@__PURE__*/
+  messageDesc(file_common, 2);
+
 /**
  * @generated from message common.FrameInfo
  */
@@ -139,7 +182,7 @@ export type FrameInfo = Message<"common.FrameInfo"> & {
  * Use `create(FrameInfoSchema)` to create a new message.
  */
 export const FrameInfoSchema: GenMessage<FrameInfo> = /*@__PURE__*/
-  messageDesc(file_common, 2);
+  messageDesc(file_common, 3);
 
 /**
  * @generated from message common.Parameter
@@ -183,6 +226,38 @@ export type Parameter = Message<"common.Parameter"> & {
      */
     value: Color;
     case: "colorValue";
+  } | {
+    /**
+     * The value of the selected option.
+     *
+     * @generated from field: string select_value = 7;
+     */
+    value: string;
+    case: "selectValue";
+  } | {
+    /**
+     * @generated from field: common.FileValue file_value = 8;
+     */
+    value: FileValue;
+    case: "fileValue";
+  } | {
+    /**
+     * @generated from field: common.Vector2 vector2_value = 9;
+     */
+    value: Vector2;
+    case: "vector2Value";
+  } | {
+    /**
+     * @generated from field: common.Vector3 vector3_value = 10;
+     */
+    value: Vector3;
+    case: "vector3Value";
+  } | {
+    /**
+     * @generated from field: common.Range range_value = 11;
+     */
+    value: Range;
+    case: "rangeValue";
   } | { case: undefined; value?: undefined };
 };
 
@@ -191,7 +266,109 @@ export type Parameter = Message<"common.Parameter"> & {
  * Use `create(ParameterSchema)` to create a new message.
  */
 export const ParameterSchema: GenMessage<Parameter> = /*@__PURE__*/
-  messageDesc(file_common, 3);
+  messageDesc(file_common, 4);
+
+/**
+ * @generated from message common.Vector2
+ */
+export type Vector2 = Message<"common.Vector2"> & {
+  /**
+   * @generated from field: double x = 1;
+   */
+  x: number;
+
+  /**
+   * @generated from field: double y = 2;
+   */
+  y: number;
+};
+
+/**
+ * Describes the message common.Vector2.
+ * Use `create(Vector2Schema)` to create a new message.
+ */
+export const Vector2Schema: GenMessage<Vector2> = /*@__PURE__*/
+  messageDesc(file_common, 5);
+
+/**
+ * @generated from message common.Vector3
+ */
+export type Vector3 = Message<"common.Vector3"> & {
+  /**
+   * @generated from field: double x = 1;
+   */
+  x: number;
+
+  /**
+   * @generated from field: double y = 2;
+   */
+  y: number;
+
+  /**
+   * @generated from field: double z = 3;
+   */
+  z: number;
+};
+
+/**
+ * Describes the message common.Vector3.
+ * Use `create(Vector3Schema)` to create a new message.
+ */
+export const Vector3Schema: GenMessage<Vector3> = /*@__PURE__*/
+  messageDesc(file_common, 6);
+
+/**
+ * @generated from message common.Range
+ */
+export type Range = Message<"common.Range"> & {
+  /**
+   * @generated from field: double start = 1;
+   */
+  start: number;
+
+  /**
+   * @generated from field: double end = 2;
+   */
+  end: number;
+};
+
+/**
+ * Describes the message common.Range.
+ * Use `create(RangeSchema)` to create a new message.
+ */
+export const RangeSchema: GenMessage<Range> = /*@__PURE__*/
+  messageDesc(file_common, 7);
+
+/**
+ * @generated from message common.FileValue
+ */
+export type FileValue = Message<"common.FileValue"> & {
+  /**
+   * @generated from field: string path = 1;
+   */
+  path: string;
+
+  /**
+   * Modification time in milliseconds since the Unix epoch, 0 if the file does not exist.
+   *
+   * @generated from field: int64 modified_ms = 2;
+   */
+  modifiedMs: bigint;
+
+  /**
+   * Filled in by vi5-cef-server so that the page can read the file.
+   *
+   * @generated from field: bytes data = 3;
+   */
+  data: Uint8Array;
+};
+
+/**
+ * Describes the message common.FileValue.
+ * Use `create(FileValueSchema)` to create a new message.
+ */
+export const FileValueSchema: GenMessage<FileValue> = /*@__PURE__*/
+  messageDesc(file_common, 8);
 
 /**
  * @generated from message common.Color
@@ -223,7 +400,7 @@ export type Color = Message<"common.Color"> & {
  * Use `create(ColorSchema)` to create a new message.
  */
 export const ColorSchema: GenMessage<Color> = /*@__PURE__*/
-  messageDesc(file_common, 4);
+  messageDesc(file_common, 9);
 
 /**
  * @generated from message common.ObjectInfo
@@ -243,6 +420,32 @@ export type ObjectInfo = Message<"common.ObjectInfo"> & {
    * @generated from field: repeated common.ParameterDefinition parameter_definitions = 3;
    */
   parameterDefinitions: ParameterDefinition[];
+
+  /**
+   * The object accumulates state across frames, so frames must be drawn in order.
+   *
+   * @generated from field: bool stateful = 4;
+   */
+  stateful: boolean;
+
+  /**
+   * Hash of the object's source, used to invalidate persistent caches.
+   *
+   * @generated from field: string version = 5;
+   */
+  version: string;
+
+  /**
+   * Inputs the rendered image depends on. When unset, the object depends on everything.
+   *
+   * @generated from field: optional common.ObjectDependencies dependencies = 6;
+   */
+  dependencies?: ObjectDependencies;
+
+  /**
+   * @generated from field: common.ObjectKind kind = 7;
+   */
+  kind: ObjectKind;
 };
 
 /**
@@ -250,7 +453,43 @@ export type ObjectInfo = Message<"common.ObjectInfo"> & {
  * Use `create(ObjectInfoSchema)` to create a new message.
  */
 export const ObjectInfoSchema: GenMessage<ObjectInfo> = /*@__PURE__*/
-  messageDesc(file_common, 5);
+  messageDesc(file_common, 10);
+
+/**
+ * @generated from message common.ObjectDependencies
+ */
+export type ObjectDependencies = Message<"common.ObjectDependencies"> & {
+  /**
+   * current/total frame and time, framerate
+   *
+   * @generated from field: bool time = 1;
+   */
+  time: boolean;
+
+  /**
+   * x, y, z
+   *
+   * @generated from field: bool position = 2;
+   */
+  position: boolean;
+
+  /**
+   * @generated from field: bool screen_size = 3;
+   */
+  screenSize: boolean;
+
+  /**
+   * @generated from field: bool parameters = 4;
+   */
+  parameters: boolean;
+};
+
+/**
+ * Describes the message common.ObjectDependencies.
+ * Use `create(ObjectDependenciesSchema)` to create a new message.
+ */
+export const ObjectDependenciesSchema: GenMessage<ObjectDependencies> = /*@__PURE__*/
+  messageDesc(file_common, 11);
 
 /**
  * @generated from message common.ParameterString
@@ -263,7 +502,7 @@ export type ParameterString = Message<"common.ParameterString"> & {
  * Use `create(ParameterStringSchema)` to create a new message.
  */
 export const ParameterStringSchema: GenMessage<ParameterString> = /*@__PURE__*/
-  messageDesc(file_common, 6);
+  messageDesc(file_common, 12);
 
 /**
  * @generated from message common.ParameterText
@@ -276,7 +515,7 @@ export type ParameterText = Message<"common.ParameterText"> & {
  * Use `create(ParameterTextSchema)` to create a new message.
  */
 export const ParameterTextSchema: GenMessage<ParameterText> = /*@__PURE__*/
-  messageDesc(file_common, 7);
+  messageDesc(file_common, 13);
 
 /**
  * @generated from message common.ParameterBoolean
@@ -289,7 +528,7 @@ export type ParameterBoolean = Message<"common.ParameterBoolean"> & {
  * Use `create(ParameterBooleanSchema)` to create a new message.
  */
 export const ParameterBooleanSchema: GenMessage<ParameterBoolean> = /*@__PURE__*/
-  messageDesc(file_common, 8);
+  messageDesc(file_common, 14);
 
 /**
  * @generated from message common.ParameterNumber
@@ -316,7 +555,7 @@ export type ParameterNumber = Message<"common.ParameterNumber"> & {
  * Use `create(ParameterNumberSchema)` to create a new message.
  */
 export const ParameterNumberSchema: GenMessage<ParameterNumber> = /*@__PURE__*/
-  messageDesc(file_common, 9);
+  messageDesc(file_common, 15);
 
 /**
  * @generated from message common.ParameterColor
@@ -329,7 +568,138 @@ export type ParameterColor = Message<"common.ParameterColor"> & {
  * Use `create(ParameterColorSchema)` to create a new message.
  */
 export const ParameterColorSchema: GenMessage<ParameterColor> = /*@__PURE__*/
-  messageDesc(file_common, 10);
+  messageDesc(file_common, 16);
+
+/**
+ * @generated from message common.SelectOption
+ */
+export type SelectOption = Message<"common.SelectOption"> & {
+  /**
+   * @generated from field: string label = 1;
+   */
+  label: string;
+
+  /**
+   * @generated from field: string value = 2;
+   */
+  value: string;
+};
+
+/**
+ * Describes the message common.SelectOption.
+ * Use `create(SelectOptionSchema)` to create a new message.
+ */
+export const SelectOptionSchema: GenMessage<SelectOption> = /*@__PURE__*/
+  messageDesc(file_common, 17);
+
+/**
+ * @generated from message common.ParameterSelect
+ */
+export type ParameterSelect = Message<"common.ParameterSelect"> & {
+  /**
+   * @generated from field: repeated common.SelectOption options = 1;
+   */
+  options: SelectOption[];
+};
+
+/**
+ * Describes the message common.ParameterSelect.
+ * Use `create(ParameterSelectSchema)` to create a new message.
+ */
+export const ParameterSelectSchema: GenMessage<ParameterSelect> = /*@__PURE__*/
+  messageDesc(file_common, 18);
+
+/**
+ * Each component is edited as a separate number.
+ *
+ * @generated from message common.ParameterVector2
+ */
+export type ParameterVector2 = Message<"common.ParameterVector2"> & {
+  /**
+   * @generated from field: common.ParameterNumber x = 1;
+   */
+  x?: ParameterNumber;
+
+  /**
+   * @generated from field: common.ParameterNumber y = 2;
+   */
+  y?: ParameterNumber;
+};
+
+/**
+ * Describes the message common.ParameterVector2.
+ * Use `create(ParameterVector2Schema)` to create a new message.
+ */
+export const ParameterVector2Schema: GenMessage<ParameterVector2> = /*@__PURE__*/
+  messageDesc(file_common, 19);
+
+/**
+ * @generated from message common.ParameterVector3
+ */
+export type ParameterVector3 = Message<"common.ParameterVector3"> & {
+  /**
+   * @generated from field: common.ParameterNumber x = 1;
+   */
+  x?: ParameterNumber;
+
+  /**
+   * @generated from field: common.ParameterNumber y = 2;
+   */
+  y?: ParameterNumber;
+
+  /**
+   * @generated from field: common.ParameterNumber z = 3;
+   */
+  z?: ParameterNumber;
+};
+
+/**
+ * Describes the message common.ParameterVector3.
+ * Use `create(ParameterVector3Schema)` to create a new message.
+ */
+export const ParameterVector3Schema: GenMessage<ParameterVector3> = /*@__PURE__*/
+  messageDesc(file_common, 20);
+
+/**
+ * @generated from message common.ParameterRange
+ */
+export type ParameterRange = Message<"common.ParameterRange"> & {
+  /**
+   * @generated from field: common.ParameterNumber start = 1;
+   */
+  start?: ParameterNumber;
+
+  /**
+   * @generated from field: common.ParameterNumber end = 2;
+   */
+  end?: ParameterNumber;
+};
+
+/**
+ * Describes the message common.ParameterRange.
+ * Use `create(ParameterRangeSchema)` to create a new message.
+ */
+export const ParameterRangeSchema: GenMessage<ParameterRange> = /*@__PURE__*/
+  messageDesc(file_common, 21);
+
+/**
+ * @generated from message common.ParameterFile
+ */
+export type ParameterFile = Message<"common.ParameterFile"> & {
+  /**
+   * File name patterns such as "*.png".
+   *
+   * @generated from field: repeated string filters = 1;
+   */
+  filters: string[];
+};
+
+/**
+ * Describes the message common.ParameterFile.
+ * Use `create(ParameterFileSchema)` to create a new message.
+ */
+export const ParameterFileSchema: GenMessage<ParameterFile> = /*@__PURE__*/
+  messageDesc(file_common, 22);
 
 /**
  * @generated from message common.ParameterType
@@ -368,6 +738,36 @@ export type ParameterType = Message<"common.ParameterType"> & {
      */
     value: ParameterColor;
     case: "color";
+  } | {
+    /**
+     * @generated from field: common.ParameterSelect select = 6;
+     */
+    value: ParameterSelect;
+    case: "select";
+  } | {
+    /**
+     * @generated from field: common.ParameterFile file = 7;
+     */
+    value: ParameterFile;
+    case: "file";
+  } | {
+    /**
+     * @generated from field: common.ParameterVector2 vector2 = 8;
+     */
+    value: ParameterVector2;
+    case: "vector2";
+  } | {
+    /**
+     * @generated from field: common.ParameterVector3 vector3 = 9;
+     */
+    value: ParameterVector3;
+    case: "vector3";
+  } | {
+    /**
+     * @generated from field: common.ParameterRange range = 10;
+     */
+    value: ParameterRange;
+    case: "range";
   } | { case: undefined; value?: undefined };
 };
 
@@ -376,7 +776,7 @@ export type ParameterType = Message<"common.ParameterType"> & {
  * Use `create(ParameterTypeSchema)` to create a new message.
  */
 export const ParameterTypeSchema: GenMessage<ParameterType> = /*@__PURE__*/
-  messageDesc(file_common, 11);
+  messageDesc(file_common, 23);
 
 /**
  * @generated from message common.ParameterDefinition
@@ -401,6 +801,27 @@ export type ParameterDefinition = Message<"common.ParameterDefinition"> & {
    * @generated from field: common.Parameter default_value = 4;
    */
   defaultValue?: Parameter;
+
+  /**
+   * Parameters with the same group are shown together. Empty for no group.
+   *
+   * @generated from field: string group = 5;
+   */
+  group: string;
+
+  /**
+   * Longer explanation of the parameter, for tooltips and documentation.
+   *
+   * @generated from field: string description = 6;
+   */
+  description: string;
+
+  /**
+   * Parameters are sorted by this, then by declaration order. Unset sorts last.
+   *
+   * @generated from field: optional int32 order = 7;
+   */
+  order?: number;
 };
 
 /**
@@ -408,7 +829,7 @@ export type ParameterDefinition = Message<"common.ParameterDefinition"> & {
  * Use `create(ParameterDefinitionSchema)` to create a new message.
  */
 export const ParameterDefinitionSchema: GenMessage<ParameterDefinition> = /*@__PURE__*/
-  messageDesc(file_common, 12);
+  messageDesc(file_common, 24);
 
 /**
  * @generated from message common.BatchRenderRequest
@@ -425,7 +846,32 @@ export type BatchRenderRequest = Message<"common.BatchRenderRequest"> & {
  * Use `create(BatchRenderRequestSchema)` to create a new message.
  */
 export const BatchRenderRequestSchema: GenMessage<BatchRenderRequest> = /*@__PURE__*/
-  messageDesc(file_common, 13);
+  messageDesc(file_common, 25);
+
+/**
+ * @generated from enum common.ObjectKind
+ */
+export enum ObjectKind {
+  /**
+   * Draws an image from scratch.
+   *
+   * @generated from enum value: OBJECT_KIND_GENERATOR = 0;
+   */
+  GENERATOR = 0,
+
+  /**
+   * Transforms the image of the object it is applied to.
+   *
+   * @generated from enum value: OBJECT_KIND_FILTER = 1;
+   */
+  FILTER = 1,
+}
+
+/**
+ * Describes the enum common.ObjectKind.
+ */
+export const ObjectKindSchema: GenEnum<ObjectKind> = /*@__PURE__*/
+  enumDesc(file_common, 0);
 
 /**
  * @generated from enum common.NumberStep
@@ -456,5 +902,5 @@ export enum NumberStep {
  * Describes the enum common.NumberStep.
  */
 export const NumberStepSchema: GenEnum<NumberStep> = /*@__PURE__*/
-  enumDesc(file_common, 0);
+  enumDesc(file_common, 1);
 
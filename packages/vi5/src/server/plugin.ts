@@ -1,5 +1,6 @@
 import type { Plugin } from "vite";
 import fs from "node:fs/promises";
+import { createHash } from "node:crypto";
 import index from "./index.html?raw";
 import { dedent } from "../helpers/dedent";
 import type { Config } from "../config";
@@ -74,6 +75,10 @@ export function createVi5Plugin(config: Config, restartServer: () => Promise<voi
             projectName: config.name,
             objectList: await buildObjectList(),
             hookConsoleLog: config.hookConsoleLog ?? true,
+            checkpoint: {
+              interval: config.checkpoint?.interval ?? 60,
+              memoryBudget: config.checkpoint?.memoryBudget ?? 256 * 1024 * 1024,
+            },
           },
         },
         // resolve: {
@@ -88,16 +93,19 @@ export function createVi5Plugin(config: Config, restartServer: () => Promise<voi
         id: /.*\.object\.ts$/,
       },
       async handler(code, _id) {
+        // ディスクキャッシュの無効化に使う。importしているファイルの変更までは追えない
+        const version = createHash("sha256").update(code).digest("hex");
         return (
           code +
           "\n" +
           dedent(`
+        export const __vi5_version = ${JSON.stringify(version)};
         let __vi5_objectId = null;
         export const __vi5_setObjectId = (id) => (__vi5_objectId = id);
         if (import.meta.hot) {
           import.meta.hot.accept((newModule) => {
             if (newModule?.default) {
-              window.__vi5__.register(newModule.default);
+              window.__vi5__.register(newModule.default, newModule.__vi5_version);
               newModule.__vi5_setObjectId(newModule.default.id);
             }
           });
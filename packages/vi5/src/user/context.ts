@@ -1,5 +1,5 @@
 import p5 from "p5";
-import type { FrameInfo } from "../gen/common_pb";
+import type { FrameInfo, InputImage } from "../gen/common_pb";
 p5.disableFriendlyErrors = true;
 
 function setProperty<T extends keyof p5>(p: p5, property: T, value: p5[T]) {
@@ -11,6 +11,7 @@ export class Vi5Context {
   #mainCanvas: p5.Renderer | null = null;
   #graphics: p5.Graphics[] = [];
   #frameInfo: FrameInfo | null = null;
+  #inputImage: p5.Image | null = null;
 
   /** @internal */
   constructor() {
@@ -61,6 +62,30 @@ export class Vi5Context {
     return this.#frameInfo;
   }
 
+  /** フィルターの場合、適用先のオブジェクトの画像。フィルター以外では null。 */
+  get inputImage(): p5.Image | null {
+    return this.#inputImage;
+  }
+
+  /** @internal */
+  setInputImage(image: InputImage | undefined) {
+    if (!image) {
+      this.#inputImage = null;
+      return;
+    }
+    // 大きさが変わらない限り同じ画像を使い回す
+    if (
+      !this.#inputImage ||
+      this.#inputImage.width !== image.width ||
+      this.#inputImage.height !== image.height
+    ) {
+      this.#inputImage = this.p.createImage(image.width, image.height);
+    }
+    this.#inputImage.loadPixels();
+    this.#inputImage.pixels.set(image.imageData);
+    this.#inputImage.updatePixels();
+  }
+
   /** @internal */
   setFrameInfo(frameInfo: FrameInfo) {
     this.#frameInfo = frameInfo;
@@ -8,13 +8,42 @@ export const parameterTypes = {
   number: "number",
   boolean: "boolean",
   color: "color",
+  select: "select",
+  file: "file",
+  vector2: "vector2",
+  vector3: "vector3",
+  range: "range",
 } as const;
 
+/** 各成分は0〜255。アルファは乗算されていない。 */
 export type Color = {
   r: number;
   g: number;
   b: number;
-  a: 0 | 255;
+  a: number;
+};
+
+/** ファイルパラメーターの値。`url` は p.loadImage などでそのまま読める。未選択なら両方とも空文字列。 */
+export type FileParameter = {
+  path: string;
+  url: string;
+};
+
+export type Vector2 = {
+  x: number;
+  y: number;
+};
+
+export type Vector3 = {
+  x: number;
+  y: number;
+  z: number;
+};
+
+/** 範囲。AviUtl2 では開始と終了が別のトラックバーになり、`start <= end` に並べ替えて渡される。 */
+export type Range = {
+  start: number;
+  end: number;
 };
 
 export type ParameterType<T extends keyof typeof parameterTypes> = T extends "string"
@@ -27,7 +56,17 @@ export type ParameterType<T extends keyof typeof parameterTypes> = T extends "st
         ? boolean
         : T extends "color"
           ? Color
-          : never;
+          : T extends "select"
+            ? string
+            : T extends "file"
+              ? FileParameter
+              : T extends "vector2"
+                ? Vector2
+                : T extends "vector3"
+                  ? Vector3
+                  : T extends "range"
+                    ? Range
+                    : never;
 
 export const numberStep = {
   "1": NumberStep.ONE,
@@ -36,32 +75,131 @@ export const numberStep = {
   "0.001": NumberStep.POINT_ZERO_ZERO_ONE,
 } as const;
 
-type ParameterDefinition<T extends keyof typeof parameterTypes> = T extends "number"
-  ? {
-      type: T;
-      label: string;
-      default: ParameterType<T>;
-      step: NumberStep;
-      min: number;
-      max: number;
-    }
-  : {
-      type: T;
-      label: string;
-      default: ParameterType<T>;
-    };
+/** ベクトルや範囲の成分ごとの設定。 */
+export type NumberComponent = {
+  step: NumberStep;
+  min: number;
+  max: number;
+};
+
+/** 選択肢。`label` が AviUtl2 に表示され、`value` がパラメーターの値になる。 */
+export type SelectOption = {
+  label: string;
+  value: string;
+};
+
+/** 全てのパラメーターに共通する、表示のための設定。 */
+type ParameterPresentation = {
+  /** 同じグループのパラメーターはまとめて表示される。 */
+  group?: string;
+  /** パラメーターの説明。 */
+  description?: string;
+  /** 小さいものから順に並ぶ。省略したものは最後に、宣言順に並ぶ。 */
+  order?: number;
+};
+
+type ParameterDefinition<T extends keyof typeof parameterTypes> = ParameterPresentation &
+  (T extends "number"
+    ? {
+        type: T;
+        label: string;
+        default: ParameterType<T>;
+        step: NumberStep;
+        min: number;
+        max: number;
+      }
+    : T extends "select"
+      ? {
+          type: T;
+          label: string;
+          default: ParameterType<T>;
+          options: SelectOption[];
+        }
+      : T extends "file"
+        ? {
+            type: T;
+            label: string;
+            /** "*.png" のようなパターン。最初は何も選ばれていない */
+            filters?: string[];
+          }
+        : T extends "vector2"
+          ? {
+              type: T;
+              label: string;
+              default: ParameterType<T>;
+              x: NumberComponent;
+              y: NumberComponent;
+            }
+          : T extends "vector3"
+            ? {
+                type: T;
+                label: string;
+                default: ParameterType<T>;
+                x: NumberComponent;
+                y: NumberComponent;
+                z: NumberComponent;
+              }
+            : T extends "range"
+              ? {
+                  type: T;
+                  label: string;
+                  default: ParameterType<T>;
+                  start: NumberComponent;
+                  end: NumberComponent;
+                }
+              : {
+                  type: T;
+                  label: string;
+                  default: ParameterType<T>;
+                });
 export type InferParameters<
   T extends Record<string, ParameterDefinition<keyof typeof parameterTypes>>,
 > = {
   [K in keyof T]: ParameterType<T[K]["type"]>;
 };
 
+/** 描画結果が依存する入力。省略したものは依存するとみなす。 */
+export type ObjectDependencies = {
+  /** フレーム番号・時刻・フレームレート */
+  time?: boolean;
+  /** オブジェクトの座標 */
+  position?: boolean;
+  /** 画面サイズ */
+  screenSize?: boolean;
+  /** パラメーター */
+  parameters?: boolean;
+};
+
 export type ParameterDefinitions = Record<string, ParameterDefinition<keyof typeof parameterTypes>>;
 export type Vi5Object<T extends ParameterDefinitions> = {
   id: string;
   label: string;
 
   parameters: T;
+  /**
+   * "filter" にするとフィルター効果になり、適用先のオブジェクトの画像が `ctx.inputImage` で渡される。
+   * 描画結果はオブジェクトの画像を置き換える。省略すると画像を生成するオブジェクトになる。
+   */
+  kind?: "object" | "filter";
+  /** フレームをまたいで状態を持つ場合はtrueにする。シーク時に途中のフレームが順番に描画される。 */
+  stateful?: boolean;
+  /**
+   * 描画結果が依存する入力。依存しない入力が変わっても再描画されなくなる。
+   * 例えばパラメーターだけで見た目が決まるなら `{ time: false, position: false }` とすると、1回だけ描画される。
+   */
+  dependsOn?: ObjectDependencies;
+  /**
+   * stateful なオブジェクトの状態を保存する。一定フレームごとと、プレビューでは描画の直前にも呼ばれ、
+   * シーク時や同じフレームを描き直すときに restore で復元される。
+   * 返り値はこの後の描画で書き換えられないよう複製しておくこと。
+   */
+  snapshot?: (ctx: Vi5Context, p: p5) => unknown;
+  /** snapshot で保存した状態に戻す。渡された値は再利用されるので書き換えないこと。 */
+  restore?: (ctx: Vi5Context, p: p5, snapshot: unknown) => void;
+  /**
+   * 書き出しでは時計と乱数が固定されるが、非同期の場合、最初の `await` より後で固定されるのは
+   * `p.millis()` と `p.random()` だけになる。`Math.random` や `Date.now` は使わないこと。
+   */
   setup: (ctx: Vi5Context, p: p5, params: InferParameters<T>) => Promise<p5.Renderer> | p5.Renderer;
   draw: (ctx: Vi5Context, p: p5, params: InferParameters<T>) => void;
 };